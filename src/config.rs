@@ -0,0 +1,130 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Per repository defaults, read from an `ent.toml`
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Name of the config file looked for
+pub const FILE_NAME: &str = "ent.toml";
+
+/// Errors returned while loading the config file
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Unable to read config {0}: {1}")]
+    IO(PathBuf, #[source] io::Error),
+
+    #[error("Config {0} is invalid: {1}")]
+    Invalid(PathBuf, #[source] toml::de::Error),
+}
+
+/// Defaults for command line options, overridden by any flags given
+///
+/// ```toml
+/// root = "recipes"
+/// jobs = 16
+/// cache_ttl = "1d"
+/// github_token = "..."
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Root of the recipe tree, relative to the config file
+    pub root: Option<PathBuf>,
+
+    /// Maximum number of concurrent network requests
+    pub jobs: Option<usize>,
+
+    /// How long cached versions are used, such as `6h`
+    pub cache_ttl: Option<String>,
+
+    /// Token used to authenticate against the GitHub API
+    pub github_token: Option<String>,
+
+    /// The config file this was loaded from, if any
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Load the nearest `ent.toml`, looking in `cwd` and then each of its parents
+    ///
+    /// Not finding a config file isn't an error, and yields the empty config. A
+    /// relative `root` is resolved against the directory of the config file.
+    pub fn load(cwd: impl AsRef<Path>) -> Result<Self, Error> {
+        let Some(path) = cwd
+            .as_ref()
+            .ancestors()
+            .map(|dir| dir.join(FILE_NAME))
+            .find(|path| path.is_file())
+        else {
+            return Ok(Self::default());
+        };
+
+        let contents = fs::read_to_string(&path).map_err(|e| Error::IO(path.clone(), e))?;
+        let mut config: Config =
+            toml::from_str(&contents).map_err(|e| Error::Invalid(path.clone(), e))?;
+
+        if let (Some(root), Some(dir)) = (&config.root, path.parent()) {
+            config.root = Some(dir.join(root));
+        }
+        config.path = Some(path);
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_finds_the_nearest_config() {
+        let root = tempfile::tempdir().unwrap();
+        let nested = root.path().join("repo/recipes/nano");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.path().join(FILE_NAME), "jobs = 4\n").unwrap();
+        fs::write(
+            root.path().join("repo").join(FILE_NAME),
+            "root = \"recipes\"\njobs = 8\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&nested).unwrap();
+
+        assert_eq!(config.path, Some(root.path().join("repo").join(FILE_NAME)));
+        assert_eq!(config.jobs, Some(8));
+        assert_eq!(config.root, Some(root.path().join("repo/recipes")));
+
+        let config = Config::load(root.path()).unwrap();
+        assert_eq!(config.jobs, Some(4));
+        assert_eq!(config.root, None);
+    }
+
+    #[test]
+    fn missing_config_is_empty() {
+        // The temporary directory's parents aren't expected to hold an ent.toml
+        let root = tempfile::tempdir().unwrap();
+        let config = Config::load(root.path()).unwrap();
+
+        assert!(config.path.is_none());
+        assert!(config.jobs.is_none());
+    }
+
+    #[test]
+    fn invalid_config_names_the_file() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join(FILE_NAME), "threads = 4\n").unwrap();
+
+        let err = Config::load(root.path()).unwrap_err();
+
+        assert!(matches!(&err, Error::Invalid(path, _) if path.ends_with(FILE_NAME)));
+    }
+}
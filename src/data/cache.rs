@@ -0,0 +1,195 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! On-disk cache of upstream versions, written by `ent refresh`
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::updates::VersionResponse;
+use crate::state;
+
+/// How long cached versions are used before they are fetched again
+pub const DEFAULT_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// The current time as a unix timestamp
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Parse a TTL such as `6h`, `30m`, `90s` or a plain number of seconds
+pub fn parse_ttl(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (digits, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let value = digits
+        .parse::<u64>()
+        .map_err(|_| format!("invalid TTL `{s}`, expected e.g. 6h, 30m or 90s"))?;
+    let seconds = match unit {
+        "s" => value,
+        "m" => value * 60,
+        "h" => value * 60 * 60,
+        "d" => value * 60 * 60 * 24,
+        _ => return Err(format!("invalid TTL unit `{unit}`, expected s, m, h or d")),
+    };
+    Ok(Duration::from_secs(seconds))
+}
+
+/// A cached release-monitoring.org response for a single project
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Entry {
+    /// Unix timestamp of when the versions were fetched
+    pub fetched_at: i64,
+
+    /// Recipe files monitoring this project
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+
+    pub versions: VersionResponse,
+}
+
+impl Entry {
+    /// Whether the entry was fetched within `ttl` of `now`
+    pub fn is_fresh(&self, now: i64, ttl: Duration) -> bool {
+        now.saturating_sub(self.fetched_at) < ttl.as_secs() as i64
+    }
+}
+
+/// The version cache, keyed by release-monitoring.org project id
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct VersionCache {
+    #[serde(default)]
+    pub entries: BTreeMap<i64, Entry>,
+}
+
+impl VersionCache {
+    /// The default location of the cache, `$XDG_CACHE_HOME/ent/versions.json`,
+    /// falling back to `~/.cache/ent/versions.json`
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+        Some(base.join("ent").join("versions.json"))
+    }
+
+    /// Load the cache from disk
+    ///
+    /// A missing cache is empty rather than an error. A cache that can't be parsed
+    /// yields an `InvalidData` error so the caller can discard it.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Atomically write the cache to disk, creating its directory as needed
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string_pretty(self).map_err(io::Error::from)?;
+        state::write_atomic(path, contents)
+    }
+
+    /// The cached versions of a project, unless missing or older than `ttl`
+    pub fn fresh(&self, project_id: i64, now: i64, ttl: Duration) -> Option<&VersionResponse> {
+        self.entries
+            .get(&project_id)
+            .filter(|e| e.is_fresh(now, ttl))
+            .map(|e| &e.versions)
+    }
+
+    /// Drop entries for which none of the recipe paths exist anymore, returning
+    /// how many were removed
+    pub fn prune(&mut self, exists: impl Fn(&Path) -> bool) -> usize {
+        let before = self.entries.len();
+        self.entries
+            .retain(|_, entry| entry.paths.iter().any(|p| exists(p)));
+        before - self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // This function builds a cache entry fetched at the given time
+    fn entry(fetched_at: i64, latest: &str, paths: &[&str]) -> Entry {
+        Entry {
+            fetched_at,
+            paths: paths.iter().map(PathBuf::from).collect(),
+            versions: VersionResponse {
+                latest_version: Some(latest.to_string()),
+                stable_versions: vec![latest.to_string()],
+                versions: vec![latest.to_string()],
+            },
+        }
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ent").join("versions.json");
+        let mut cache = VersionCache::default();
+        cache
+            .entries
+            .insert(2046, entry(1_700_000_000, "8.0", &["nano/package.yml"]));
+        cache.entries.insert(5092, entry(1_700_000_100, "9.1", &[]));
+
+        cache.save(&path).unwrap();
+        let loaded = VersionCache::load(&path).unwrap();
+
+        assert_eq!(loaded.entries.len(), 2);
+        let nano = &loaded.entries[&2046];
+        assert_eq!(nano.fetched_at, 1_700_000_000);
+        assert_eq!(nano.paths, [PathBuf::from("nano/package.yml")]);
+        assert_eq!(nano.versions.latest_version.as_deref(), Some("8.0"));
+        assert_eq!(nano.versions.stable_versions, ["8.0"]);
+        assert_eq!(loaded.entries[&5092].versions.versions, ["9.1"]);
+    }
+
+    #[test]
+    fn missing_cache_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = VersionCache::load(dir.path().join("versions.json")).unwrap();
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn corrupt_cache_is_invalid_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("versions.json");
+        fs::write(&path, "{ not json").unwrap();
+
+        let error = VersionCache::load(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn stale_entries_are_not_fresh() {
+        let mut cache = VersionCache::default();
+        cache.entries.insert(2046, entry(1000, "8.0", &[]));
+        let ttl = Duration::from_secs(60);
+
+        assert!(cache.fresh(2046, 1059, ttl).is_some());
+        assert!(cache.fresh(2046, 1060, ttl).is_none());
+        assert!(cache.fresh(5092, 1000, ttl).is_none());
+    }
+}
@@ -0,0 +1,125 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! A minimal HTTP server to drive the remote data sources against in tests
+
+use std::sync::{Arc, Mutex};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+/// A canned response of the mock server
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+}
+
+impl Response {
+    /// A successful response with a JSON body
+    pub fn json(body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("content-type", "application/json".to_string())],
+            body: body.into(),
+        }
+    }
+
+    /// An empty response with the given status
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: String::new(),
+        }
+    }
+
+    /// Add a header to the response
+    pub fn header(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+}
+
+/// An HTTP server on a random local port, answering every request with the
+/// response its handler returns for the request's index and target
+pub struct Server {
+    /// Base URL of the server, without a trailing slash
+    pub url: String,
+
+    // Targets of the requests received so far, in order of arrival
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl Server {
+    /// Start serving on a background task, which lives as long as the runtime
+    pub async fn start(handler: impl Fn(usize, &str) -> Response + Send + Sync + 'static) -> Self {
+        Self::start_at("127.0.0.1:0", handler).await
+    }
+
+    /// Start serving on the given address, such as one a client already tried
+    pub async fn start_at(
+        addr: &str,
+        handler: impl Fn(usize, &str) -> Response + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let handler = Arc::new(handler);
+
+        tokio::spawn({
+            let requests = requests.clone();
+            async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let requests = requests.clone();
+                    let handler = handler.clone();
+                    tokio::spawn(async move {
+                        // Only GET requests are served, so the head is all there is
+                        let mut head = vec![];
+                        let mut chunk = [0; 1024];
+                        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                            match stream.read(&mut chunk).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(n) => head.extend_from_slice(&chunk[..n]),
+                            }
+                        }
+                        let target = String::from_utf8_lossy(&head)
+                            .split_whitespace()
+                            .nth(1)
+                            .unwrap_or_default()
+                            .to_string();
+                        let index = {
+                            let mut requests = requests.lock().unwrap();
+                            requests.push(target.clone());
+                            requests.len() - 1
+                        };
+
+                        let response = handler(index, &target);
+                        let mut out = format!(
+                            "HTTP/1.1 {} Mock\r\ncontent-length: {}\r\nconnection: close\r\n",
+                            response.status,
+                            response.body.len()
+                        );
+                        for (name, value) in &response.headers {
+                            out.push_str(&format!("{name}: {value}\r\n"));
+                        }
+                        out.push_str("\r\n");
+                        out.push_str(&response.body);
+                        let _ = stream.write_all(out.as_bytes()).await;
+                        let _ = stream.shutdown().await;
+                    });
+                }
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// The targets of the requests received so far, in order of arrival
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
@@ -2,6 +2,159 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
+pub mod cache;
+#[cfg(test)]
+pub(crate) mod mock;
 pub mod nvd;
+pub mod sources;
 pub mod summit;
 pub mod updates;
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use reqwest::IntoUrl;
+use thiserror::Error;
+
+/// Errors returned by the remote data sources
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Network access is disabled")]
+    NetworkDisabled,
+
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Summit task {0} not found")]
+    TaskNotFound(i64),
+
+    #[error("Project {0} not found on release-monitoring.org")]
+    ProjectNotFound(i64),
+
+    #[error("Still rate limited after {0} retries")]
+    RateLimited(u32),
+}
+
+/// HTTP client shared by the remote data sources
+#[derive(Clone, Default)]
+pub struct Client {
+    http: reqwest::Client,
+
+    // When set, every request fails with `Error::NetworkDisabled`
+    offline: bool,
+
+    // Invoked with the wait duration whenever a rate limit is hit
+    on_rate_limit: Option<Arc<dyn Fn(Duration) + Send + Sync>>,
+
+    // Total milliseconds spent waiting on rate limits, shared between clones
+    rate_limited_ms: Arc<AtomicU64>,
+
+    // How rejected and failed requests are retried
+    retry: Retry,
+
+    // Token used to authenticate against the GitHub API
+    github_token: Option<String>,
+}
+
+/// How requests rejected by rate limiting, failed with a server error or unable to
+/// reach the server are retried
+#[derive(Debug, Clone, Copy)]
+pub struct Retry {
+    /// How many times a request is retried before giving up
+    pub max_retries: u32,
+
+    /// The delay before the first retry, doubled on each subsequent one, unless the
+    /// server asks for a specific delay with `Retry-After`
+    pub base_delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl Retry {
+    /// The delay before the given retry, starting at 0
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay.saturating_mul(2u32.saturating_pow(attempt))
+    }
+}
+
+impl Client {
+    /// Create a new client, optionally refusing all network access
+    pub fn new(offline: bool) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            offline,
+            ..Default::default()
+        }
+    }
+
+    /// Register a callback to be notified whenever a request waits out a rate limit
+    pub fn on_rate_limit(mut self, callback: impl Fn(Duration) + Send + Sync + 'static) -> Self {
+        self.on_rate_limit = Some(Arc::new(callback));
+        self
+    }
+
+    /// Change how rejected and failed requests are retried
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// How rejected and failed requests are retried
+    pub fn retry(&self) -> Retry {
+        self.retry
+    }
+
+    /// Authenticate GitHub API requests with the given token
+    pub fn with_github_token(mut self, token: Option<String>) -> Self {
+        self.github_token = token.filter(|t| !t.is_empty());
+        self
+    }
+
+    // The token used to authenticate GitHub API requests, if any
+    pub(crate) fn github_token(&self) -> Option<&str> {
+        self.github_token.as_deref()
+    }
+
+    /// Total time spent waiting on rate limits by this client and its clones
+    pub fn rate_limited_for(&self) -> Duration {
+        Duration::from_millis(self.rate_limited_ms.load(Ordering::Relaxed))
+    }
+
+    // Wait out a rate limit, notifying the registered callback
+    pub(crate) async fn wait_rate_limit(&self, wait: Duration) {
+        if let Some(callback) = &self.on_rate_limit {
+            callback(wait);
+        }
+        self.rate_limited_ms
+            .fetch_add(wait.as_millis() as u64, Ordering::Relaxed);
+        tokio::time::sleep(wait).await;
+    }
+
+    /// Start a GET request, failing immediately when network access is disabled
+    pub fn get(&self, url: impl IntoUrl) -> Result<reqwest::RequestBuilder, Error> {
+        if self.offline {
+            return Err(Error::NetworkDisabled);
+        }
+        Ok(self.http.get(url))
+    }
+
+    /// Start a HEAD request, failing immediately when network access is disabled
+    pub fn head(&self, url: impl IntoUrl) -> Result<reqwest::RequestBuilder, Error> {
+        if self.offline {
+            return Err(Error::NetworkDisabled);
+        }
+        Ok(self.http.head(url))
+    }
+}
@@ -2,8 +2,12 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
+use std::{cmp::Ordering, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 
+pub mod v2;
+
 /// CVE Data Format specification for Common Vulnerabilities and Exposures (CVE) data
 ///
 /// This module provides types for parsing and representing CVE JSON data according to
@@ -14,6 +18,31 @@ pub struct CveData {
     pub cve_items: Vec<CveItem>,
 }
 
+/// Either schema a CVE feed may be saved in
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Feed {
+    Legacy(CveData),
+    V2(v2::CveResponse),
+}
+
+impl CveData {
+    /// Parse a CVE feed in either the legacy 1.1 feed format or as saved from the
+    /// 2.0 API, converting the latter so callers only deal with one representation
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        Ok(match serde_json::from_str(json)? {
+            Feed::Legacy(data) => data,
+            Feed::V2(response) => CveData {
+                cve_items: response
+                    .vulnerabilities
+                    .into_iter()
+                    .map(|v| v.cve.into())
+                    .collect(),
+            },
+        })
+    }
+}
+
 /// An individual CVE item containing details about a single vulnerability
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CveItem {
@@ -171,3 +200,420 @@ pub struct CvssV2 {
     #[serde(rename = "baseScore")]
     pub base_score: f64,
 }
+
+/// Severity of a vulnerability, ordered from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Severity {
+    /// No CVSS metrics are available to derive a severity from
+    Unknown,
+    None,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// Map a CVSS v3 base score onto its qualitative severity rating
+    pub fn from_v3_score(score: f64) -> Self {
+        match score {
+            s if s >= 9.0 => Severity::Critical,
+            s if s >= 7.0 => Severity::High,
+            s if s >= 4.0 => Severity::Medium,
+            s if s > 0.0 => Severity::Low,
+            _ => Severity::None,
+        }
+    }
+
+    /// Map a CVSS v2 base score onto its qualitative severity rating
+    ///
+    /// CVSS v2 has no critical or none ratings, so scores are banded into low (0-3.9),
+    /// medium (4-6.9) and high (7-10).
+    pub fn from_v2_score(score: f64) -> Self {
+        match score {
+            s if s >= 7.0 => Severity::High,
+            s if s >= 4.0 => Severity::Medium,
+            _ => Severity::Low,
+        }
+    }
+
+    /// Whether a CVE of this severity is reported at the `min` threshold
+    ///
+    /// CVEs of unknown severity are only reported when the threshold is `None`,
+    /// which reports everything.
+    pub fn meets(self, min: Severity) -> bool {
+        min <= Severity::None || self >= min
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Severity::None),
+            "low" => Ok(Severity::Low),
+            "medium" => Ok(Severity::Medium),
+            "high" => Ok(Severity::High),
+            "critical" => Ok(Severity::Critical),
+            _ => Err(format!(
+                "unknown severity `{s}`, expected none, low, medium, high or critical"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Severity::Unknown => "UNKNOWN",
+            Severity::None => "NONE",
+            Severity::Low => "LOW",
+            Severity::Medium => "MEDIUM",
+            Severity::High => "HIGH",
+            Severity::Critical => "CRITICAL",
+        };
+        f.write_str(s)
+    }
+}
+
+impl CveItem {
+    /// The single severity rating to report for this CVE
+    ///
+    /// CVSS v3 metrics are preferred when present, using the declared base severity (or
+    /// the base score should the rating be unrecognised). Otherwise the v2 base score is
+    /// banded, and CVEs with neither are `Severity::Unknown`.
+    pub fn effective_severity(&self) -> Severity {
+        if let Some(v3) = &self.impact.base_metric_v3 {
+            return match v3.cvss_v3.base_severity.to_uppercase().as_str() {
+                "NONE" => Severity::None,
+                "LOW" => Severity::Low,
+                "MEDIUM" => Severity::Medium,
+                "HIGH" => Severity::High,
+                "CRITICAL" => Severity::Critical,
+                _ => Severity::from_v3_score(v3.cvss_v3.base_score),
+            };
+        }
+
+        match &self.impact.base_metric_v2 {
+            Some(v2) => Severity::from_v2_score(v2.cvss_v2.base_score),
+            None => Severity::Unknown,
+        }
+    }
+}
+
+/// A parsed CPE 2.3 formatted string, e.g. `cpe:2.3:a:openssl:openssl:3.0.0:*:*:*:*:*:*:*`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cpe {
+    pub part: String,
+    pub vendor: String,
+    pub product: String,
+    pub version: String,
+}
+
+impl FromStr for Cpe {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Split on colons, honouring backslash escapes within components
+        let mut components = vec![];
+        let mut current = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    if let Some(escaped) = chars.next() {
+                        current.push(escaped);
+                    }
+                }
+                ':' => components.push(std::mem::take(&mut current)),
+                _ => current.push(c),
+            }
+        }
+        components.push(current);
+
+        match components.as_slice() {
+            [cpe, spec, part, vendor, product, rest @ ..] if cpe == "cpe" && spec == "2.3" => {
+                Ok(Cpe {
+                    part: part.clone(),
+                    vendor: vendor.clone(),
+                    product: product.clone(),
+                    version: rest.first().cloned().unwrap_or_else(|| "*".to_string()),
+                })
+            }
+            _ => Err(format!("invalid CPE 2.3 string `{s}`")),
+        }
+    }
+}
+
+impl CpeMatch {
+    /// Whether this rule refers to the given vendor and product
+    pub fn matches_product(&self, vendor: &str, product: &str) -> bool {
+        self.cpe23_uri.parse::<Cpe>().is_ok_and(|cpe| {
+            cpe.vendor.eq_ignore_ascii_case(vendor) && cpe.product.eq_ignore_ascii_case(product)
+        })
+    }
+
+    /// Whether the given version falls within the versions this rule affects
+    ///
+    /// Absent bounds are open ended. A rule without any bounds affects only the
+    /// version in its CPE name, where `*` and `-` stand for any version.
+    pub fn affects_version(&self, version: &str) -> bool {
+        let bounds = [
+            &self.version_start_including,
+            &self.version_start_excluding,
+            &self.version_end_including,
+            &self.version_end_excluding,
+        ];
+        if bounds.iter().all(|b| b.is_none()) {
+            return self.cpe23_uri.parse::<Cpe>().is_ok_and(|cpe| {
+                matches!(cpe.version.as_str(), "*" | "-")
+                    || compare_versions(version, &cpe.version).is_eq()
+            });
+        }
+
+        let cmp = |bound: &Option<String>| bound.as_deref().map(|b| compare_versions(version, b));
+        cmp(&self.version_start_including).is_none_or(Ordering::is_ge)
+            && cmp(&self.version_start_excluding).is_none_or(Ordering::is_gt)
+            && cmp(&self.version_end_including).is_none_or(Ordering::is_le)
+            && cmp(&self.version_end_excluding).is_none_or(Ordering::is_lt)
+    }
+}
+
+// This function compares versions numerically with `version::compare`, falling
+// back to a lexical comparison when either doesn't start with a number at all
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let numeric = |v: &str| v.starts_with(|c: char| c.is_ascii_digit());
+    if numeric(a) && numeric(b) {
+        crate::version::compare(a, b)
+    } else {
+        a.cmp(b)
+    }
+}
+
+impl Node {
+    /// All CPE match rules within this node and its children
+    pub fn cpe_matches(&self) -> Vec<&CpeMatch> {
+        let mut matches = self.cpe_match.iter().flatten().collect::<Vec<_>>();
+        for child in self.children.iter().flatten() {
+            matches.extend(child.cpe_matches());
+        }
+        matches
+    }
+}
+
+impl CveItem {
+    /// The CVE identifier, e.g. `CVE-2024-1234`
+    pub fn id(&self) -> &str {
+        &self.cve.data_meta.id
+    }
+
+    /// The CVSS base score, preferring v3 over v2 metrics
+    pub fn base_score(&self) -> Option<f64> {
+        self.impact
+            .base_metric_v3
+            .as_ref()
+            .map(|m| m.cvss_v3.base_score)
+            .or_else(|| {
+                self.impact
+                    .base_metric_v2
+                    .as_ref()
+                    .map(|m| m.cvss_v2.base_score)
+            })
+    }
+
+    /// The vulnerable CPE match rules referring to the given vendor and product
+    pub fn vulnerable_matches(&self, vendor: &str, product: &str) -> Vec<&CpeMatch> {
+        self.configurations
+            .nodes
+            .iter()
+            .flat_map(Node::cpe_matches)
+            .filter(|m| m.vulnerable && m.matches_product(vendor, product))
+            .collect()
+    }
+
+    /// Whether the given version of the vendor's product is affected by this CVE
+    pub fn affects(&self, vendor: &str, product: &str, version: &str) -> bool {
+        self.vulnerable_matches(vendor, product)
+            .iter()
+            .any(|m| m.affects_version(version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    // A response of the 2.0 API for `cpe:2.3:a:openssl:openssl`, trimmed to two CVEs
+    fn feed_v2() -> CveData {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/nvd/cves-2.0.json");
+        CveData::from_json(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parses_v2_feed() {
+        let data = feed_v2();
+        let ids = data.cve_items.iter().map(CveItem::id).collect::<Vec<_>>();
+        assert_eq!(ids, ["CVE-2023-5678", "CVE-2010-0742"]);
+
+        let item = &data.cve_items[0];
+        assert_eq!(item.published_date, "2023-11-06T16:15:42.670");
+        assert!(item.cve.description.data[0].value.starts_with("Generating"));
+        assert_eq!(
+            item.cve.references.data[0].url,
+            "https://www.openssl.org/news/secadv/20231106.txt"
+        );
+
+        let v3 = item.impact.base_metric_v3.as_ref().unwrap();
+        assert_eq!(v3.cvss_v3.base_severity, "MEDIUM");
+        assert_eq!(item.base_score(), Some(5.3));
+
+        let matches = item.vulnerable_matches("openssl", "openssl");
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].version_start_including.as_deref(), Some("3.0.0"));
+        assert_eq!(matches[0].version_end_excluding.as_deref(), Some("3.0.13"));
+    }
+
+    #[test]
+    fn matches_v2_feed_versions() {
+        let data = feed_v2();
+        let ranged = &data.cve_items[0];
+        assert!(ranged.affects("openssl", "openssl", "3.0.7"));
+        assert!(ranged.affects("openssl", "openssl", "3.1.4"));
+        assert!(!ranged.affects("openssl", "openssl", "3.0.13"));
+        assert!(!ranged.affects("openssl", "openssl", "3.2.0"));
+        assert!(!ranged.affects("gnu", "openssl", "3.0.7"));
+
+        let exact = &data.cve_items[1];
+        assert!(exact.impact.base_metric_v3.is_none());
+        assert_eq!(exact.base_score(), Some(7.5));
+        assert!(exact.affects("openssl", "openssl", "0.9.8n"));
+        assert!(!exact.affects("openssl", "openssl", "0.9.8o"));
+    }
+
+    #[test]
+    fn parses_legacy_feed() {
+        let json = r#"{
+            "CVE_Items": [{
+                "cve": {
+                    "CVE_data_meta": { "ID": "CVE-2019-1543" },
+                    "description": { "description_data": [{ "lang": "en", "value": "ChaCha20-Poly1305" }] },
+                    "references": { "reference_data": [] }
+                },
+                "configurations": { "CVE_data_version": "4.0", "nodes": [] },
+                "impact": {},
+                "lastModifiedDate": "2020-10-20T22:15Z",
+                "publishedDate": "2019-03-06T21:29Z"
+            }]
+        }"#;
+
+        let data = CveData::from_json(json).unwrap();
+        assert_eq!(data.cve_items[0].id(), "CVE-2019-1543");
+        assert_eq!(data.cve_items[0].base_score(), None);
+    }
+
+    // This function builds a vulnerable match rule for openssl with the given bounds,
+    // given as start including, start excluding, end including and end excluding
+    fn rule(bounds: [Option<&str>; 4]) -> CpeMatch {
+        let [start_including, start_excluding, end_including, end_excluding] =
+            bounds.map(|b| b.map(str::to_string));
+        CpeMatch {
+            vulnerable: true,
+            cpe23_uri: "cpe:2.3:a:openssl:openssl:*:*:*:*:*:*:*:*".to_string(),
+            version_start_including: start_including,
+            version_end_including: end_including,
+            version_start_excluding: start_excluding,
+            version_end_excluding: end_excluding,
+        }
+    }
+
+    #[test]
+    fn affects_version_honours_each_bound() {
+        let cases = [
+            // One bound of each kind, checked below, at and above the boundary
+            ([Some("3.0.0"), None, None, None], [false, true, true]),
+            ([None, Some("3.0.0"), None, None], [false, false, true]),
+            ([None, None, Some("3.0.0"), None], [true, true, false]),
+            ([None, None, None, Some("3.0.0")], [true, false, false]),
+        ];
+        for (bounds, expected) in cases {
+            let bounded = rule(bounds);
+            let affected = ["2.9.9", "3.0.0", "3.0.10"].map(|v| bounded.affects_version(v));
+            assert_eq!(affected, expected, "{bounds:?}");
+        }
+    }
+
+    #[test]
+    fn affects_version_within_a_range() {
+        let rule = rule([Some("1.1.0"), None, None, Some("1.1.10")]);
+        assert!(rule.affects_version("1.1.9"));
+        assert!(!rule.affects_version("1.1.10"));
+        assert!(!rule.affects_version("1.0.2"));
+
+        // Without bounds, only the version of the CPE name is affected
+        assert!(rule([None; 4]).affects_version("0.9.8"));
+        let mut exact = rule([None; 4]);
+        exact.cpe23_uri = "cpe:2.3:a:openssl:openssl:1.1.1:*:*:*:*:*:*:*".to_string();
+        assert!(exact.affects_version("1.1.1"));
+        assert!(!exact.affects_version("1.1.2"));
+    }
+
+    #[test]
+    fn affects_version_compares_dates_and_unparseable_versions() {
+        let dated = rule([None, None, Some("20250201"), None]);
+        assert!(dated.affects_version("20250131"));
+        assert!(dated.affects_version("20250201"));
+        assert!(!dated.affects_version("20250202"));
+
+        // Versions that don't start out numeric are compared lexically
+        assert!(!rule([None, None, None, Some("1.0")]).affects_version("snapshot"));
+        assert!(rule([None, None, None, Some("u")]).affects_version("snapshot"));
+    }
+
+    #[test]
+    fn bands_v2_scores() {
+        for (score, severity) in [
+            (0.0, Severity::Low),
+            (3.9, Severity::Low),
+            (4.0, Severity::Medium),
+            (6.9, Severity::Medium),
+            (7.0, Severity::High),
+            (10.0, Severity::High),
+        ] {
+            assert_eq!(Severity::from_v2_score(score), severity, "score {score}");
+        }
+    }
+
+    #[test]
+    fn effective_severity_falls_back_to_v2() {
+        let data = feed_v2();
+        assert_eq!(data.cve_items[0].effective_severity(), Severity::Medium);
+        assert_eq!(data.cve_items[1].effective_severity(), Severity::High);
+
+        let unscored = CveData::from_json(
+            r#"{"resultsPerPage": 1, "startIndex": 0, "totalResults": 1, "vulnerabilities": [
+                {"cve": {"id": "CVE-2024-0001", "published": "", "lastModified": ""}}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            unscored.cve_items[0].effective_severity(),
+            Severity::Unknown
+        );
+    }
+
+    #[test]
+    fn severity_thresholds() {
+        assert_eq!("HIGH".parse::<Severity>(), Ok(Severity::High));
+        assert!("severe".parse::<Severity>().is_err());
+
+        assert!(Severity::High.meets(Severity::Medium));
+        assert!(Severity::Medium.meets(Severity::Medium));
+        assert!(!Severity::Low.meets(Severity::Medium));
+        assert!(Severity::Unknown.meets(Severity::None));
+        assert!(!Severity::Unknown.meets(Severity::Low));
+    }
+}
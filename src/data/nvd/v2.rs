@@ -0,0 +1,220 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Types for the NVD CVE API 2.0
+//!
+//! Responses are converted into the legacy feed types of the parent module, so
+//! that matching and reporting only need to deal with a single representation.
+
+use serde::Deserialize;
+
+use crate::data::{Client, Error};
+
+/// Endpoint of the NVD CVE API 2.0
+const CVE_API_URL: &str = "https://services.nvd.nist.gov/rest/json/cves/2.0";
+
+/// A page of results from the CVE API
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CveResponse {
+    pub results_per_page: usize,
+    pub start_index: usize,
+    pub total_results: usize,
+    pub vulnerabilities: Vec<Vulnerability>,
+}
+
+/// A single entry of the `vulnerabilities` list
+#[derive(Debug, Deserialize)]
+pub struct Vulnerability {
+    pub cve: Cve,
+}
+
+/// Core CVE data
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cve {
+    pub id: String,
+    pub published: String,
+    pub last_modified: String,
+    #[serde(default)]
+    pub descriptions: Vec<super::DescriptionData>,
+    #[serde(default)]
+    pub metrics: Metrics,
+    #[serde(default)]
+    pub configurations: Vec<Configuration>,
+    #[serde(default)]
+    pub references: Vec<Reference>,
+}
+
+/// CVSS metrics, by CVSS version
+#[derive(Debug, Default, Deserialize)]
+pub struct Metrics {
+    #[serde(rename = "cvssMetricV31", default)]
+    pub cvss_metric_v31: Vec<CvssMetricV3>,
+    #[serde(rename = "cvssMetricV30", default)]
+    pub cvss_metric_v30: Vec<CvssMetricV3>,
+    #[serde(rename = "cvssMetricV2", default)]
+    pub cvss_metric_v2: Vec<CvssMetricV2>,
+}
+
+/// CVSS v3.x metric
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CvssMetricV3 {
+    pub cvss_data: super::CvssV3,
+    pub exploitability_score: f64,
+    pub impact_score: f64,
+}
+
+/// CVSS v2 metric
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CvssMetricV2 {
+    pub cvss_data: super::CvssV2,
+    pub exploitability_score: f64,
+    pub impact_score: f64,
+}
+
+/// A configuration describing affected products
+#[derive(Debug, Deserialize)]
+pub struct Configuration {
+    pub nodes: Vec<Node>,
+}
+
+/// Node in the configuration tree
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Node {
+    pub operator: String,
+    #[serde(default)]
+    pub cpe_match: Vec<CpeMatch>,
+}
+
+/// CPE match rules describing affected versions
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CpeMatch {
+    pub vulnerable: bool,
+    pub criteria: String,
+    pub version_start_including: Option<String>,
+    pub version_end_including: Option<String>,
+    pub version_start_excluding: Option<String>,
+    pub version_end_excluding: Option<String>,
+}
+
+/// Reference to an external source about the vulnerability
+#[derive(Debug, Deserialize)]
+pub struct Reference {
+    pub url: String,
+    pub source: Option<String>,
+}
+
+impl From<Cve> for super::CveItem {
+    fn from(cve: Cve) -> Self {
+        let metrics = cve.metrics;
+        let base_metric_v3 = metrics
+            .cvss_metric_v31
+            .into_iter()
+            .chain(metrics.cvss_metric_v30)
+            .next()
+            .map(|m| super::BaseMetricV3 {
+                cvss_v3: m.cvss_data,
+                exploitability_score: m.exploitability_score,
+                impact_score: m.impact_score,
+            });
+        let base_metric_v2 =
+            metrics
+                .cvss_metric_v2
+                .into_iter()
+                .next()
+                .map(|m| super::BaseMetricV2 {
+                    cvss_v2: m.cvss_data,
+                    exploitability_score: m.exploitability_score,
+                    impact_score: m.impact_score,
+                });
+
+        let nodes = cve
+            .configurations
+            .into_iter()
+            .flat_map(|c| c.nodes)
+            .map(|n| super::Node {
+                operator: n.operator,
+                children: None,
+                cpe_match: Some(
+                    n.cpe_match
+                        .into_iter()
+                        .map(|m| super::CpeMatch {
+                            vulnerable: m.vulnerable,
+                            cpe23_uri: m.criteria,
+                            version_start_including: m.version_start_including,
+                            version_end_including: m.version_end_including,
+                            version_start_excluding: m.version_start_excluding,
+                            version_end_excluding: m.version_end_excluding,
+                        })
+                        .collect(),
+                ),
+            })
+            .collect();
+
+        super::CveItem {
+            cve: super::Cve {
+                data_meta: super::CveDataMeta { id: cve.id },
+                description: super::Description {
+                    data: cve.descriptions,
+                },
+                references: super::References {
+                    data: cve
+                        .references
+                        .into_iter()
+                        .map(|r| super::ReferenceData {
+                            url: r.url,
+                            name: None,
+                            ref_source: r.source,
+                        })
+                        .collect(),
+                },
+            },
+            configurations: super::Configurations {
+                data_version: "2.0".to_string(),
+                nodes,
+            },
+            impact: super::Impact {
+                base_metric_v3,
+                base_metric_v2,
+            },
+            last_modified_date: cve.last_modified,
+            published_date: cve.published,
+        }
+    }
+}
+
+/// This method will fetch all CVEs affecting the given CPE name from the NVD API
+pub async fn fetch_cves(client: &Client, cpe: &str) -> Result<Vec<super::CveItem>, Error> {
+    let mut items: Vec<super::CveItem> = vec![];
+    let mut start_index = 0;
+
+    loop {
+        let response = client
+            .get(CVE_API_URL)?
+            .query(&[
+                ("cpeName", cpe),
+                ("startIndex", start_index.to_string().as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<CveResponse>()
+            .await?;
+
+        let fetched = response.vulnerabilities.len();
+        items.extend(response.vulnerabilities.into_iter().map(|v| v.cve.into()));
+
+        start_index = response.start_index + fetched;
+        if fetched == 0 || start_index >= response.total_results {
+            break;
+        }
+    }
+
+    Ok(items)
+}
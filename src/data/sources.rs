@@ -0,0 +1,40 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use reqwest::StatusCode;
+
+use super::{Client, Error};
+
+/// Liveness of an upstream source URL
+#[derive(Debug)]
+pub enum SourceStatus {
+    /// The URL resolved to something
+    Alive,
+    /// The server reports the URL as gone
+    Dead(StatusCode),
+    /// The server couldn't be reached at all
+    Unreachable(String),
+}
+
+impl SourceStatus {
+    /// Whether the source should be reported as dead
+    pub fn is_dead(&self) -> bool {
+        !matches!(self, SourceStatus::Alive)
+    }
+}
+
+/// This method will issue a HEAD request to determine whether an upstream source still exists
+///
+/// Only 404 and 410 count as dead, as plenty of servers answer HEAD requests with other
+/// errors (such as 405) for files that download fine.
+pub async fn probe(client: &Client, url: &str) -> Result<SourceStatus, Error> {
+    let status = match client.head(url)?.send().await {
+        Ok(response) => match response.status() {
+            s @ (StatusCode::NOT_FOUND | StatusCode::GONE) => SourceStatus::Dead(s),
+            _ => SourceStatus::Alive,
+        },
+        Err(e) => SourceStatus::Unreachable(e.to_string()),
+    };
+    Ok(status)
+}
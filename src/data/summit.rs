@@ -2,10 +2,59 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use serde::Deserialize;
+use std::str::FromStr;
 
-#[derive(Debug, Deserialize)]
-#[serde(from = "i32")]
+use serde::{Deserialize, Serialize};
+
+use super::{Client, Error};
+
+pub mod v2;
+
+/// Base URL of the public Summit deployment
+pub const DEFAULT_BASE_URL: &str = "https://dash.serpentos.com";
+
+/// Upper bound on how many pages of tasks are walked through, in case a
+/// misbehaving server never stops reporting a next page
+pub const MAX_PAGES: i32 = 50;
+
+// This function resolves a path given by Summit against its base URL, leaving
+// absolute URLs untouched
+fn resolve(base_url: &str, path: &str) -> String {
+    if path.starts_with("http://") || path.starts_with("https://") {
+        path.to_string()
+    } else {
+        format!(
+            "{}/{}",
+            base_url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+}
+
+/// Version of the Summit API to talk to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApiVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+impl FromStr for ApiVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" | "v1" => Ok(ApiVersion::V1),
+            "2" | "v2" => Ok(ApiVersion::V2),
+            _ => Err(format!(
+                "unknown Summit API version `{s}`, expected v1 or v2"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "i32", into = "i32")]
 pub enum BuildStatus {
     New = 0,
     Failed = 1,
@@ -28,6 +77,31 @@ impl From<i32> for BuildStatus {
         }
     }
 }
+
+impl From<BuildStatus> for i32 {
+    fn from(value: BuildStatus) -> Self {
+        value as i32
+    }
+}
+
+impl FromStr for BuildStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "new" => Ok(BuildStatus::New),
+            "failed" => Ok(BuildStatus::Failed),
+            "building" => Ok(BuildStatus::Building),
+            "publishing" => Ok(BuildStatus::Publishing),
+            "completed" => Ok(BuildStatus::Completed),
+            "blocked" => Ok(BuildStatus::Blocked),
+            _ => Err(format!(
+                "unknown build status `{s}`, expected new, failed, building, publishing, completed or blocked"
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TaskEnumerateResponse {
     pub items: Vec<Task>,
@@ -40,7 +114,11 @@ pub struct TaskEnumerateResponse {
     pub has_next: bool,
 }
 
-#[derive(Debug, Deserialize)]
+/// A Summit build task
+///
+/// Serializes with the same camelCase keys Summit uses, so the task is passed
+/// through unchanged in JSON output.
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Task {
     pub id: i64,
     #[serde(rename = "projectID")]
@@ -74,3 +152,219 @@ pub struct Task {
     #[serde(rename = "logPath")]
     pub log_path: String,
 }
+
+impl Task {
+    /// The URL of the build log, resolved against the Summit base URL
+    pub fn log_url(&self, base_url: &str) -> String {
+        resolve(base_url, &self.log_path)
+    }
+
+    /// The URL of the recipe source, resolved against the Summit base URL
+    pub fn source_url(&self, base_url: &str) -> String {
+        resolve(base_url, &self.source_path)
+    }
+}
+
+/// This method will fetch a single page of the task list from Summit
+pub async fn enumerate_tasks(
+    client: &Client,
+    base_url: &str,
+    api: ApiVersion,
+    page: i32,
+) -> Result<TaskEnumerateResponse, Error> {
+    let path = match api {
+        ApiVersion::V1 => "api/v1/tasks/enumerate",
+        ApiVersion::V2 => "api/v2/tasks/enumerate",
+    };
+    let url = resolve(base_url, path);
+    let request = client.get(format!("{}?pageNumber={}", url, page))?;
+
+    let response: TaskEnumerateResponse = match api {
+        ApiVersion::V1 => request.send().await?.json().await?,
+        ApiVersion::V2 => request
+            .send()
+            .await?
+            .json::<v2::TaskEnumerateResponse>()
+            .await?
+            .into(),
+    };
+    Ok(response)
+}
+
+/// This method will fetch every task known to Summit, walking through the pages
+/// of the task list as long as Summit reports another one
+///
+/// At most `MAX_PAGES` pages are fetched, warning when Summit still reports more,
+/// so the tasks returned may then only be the most recent ones.
+pub async fn enumerate_all_tasks(
+    client: &Client,
+    base_url: &str,
+    api: ApiVersion,
+) -> Result<Vec<Task>, Error> {
+    let mut tasks = Vec::new();
+    for page in 0..MAX_PAGES {
+        let response = enumerate_tasks(client, base_url, api, page).await?;
+        tasks.extend(response.items);
+        if !response.has_next || response.num_pages <= 0 {
+            return Ok(tasks);
+        }
+    }
+
+    tracing::warn!(
+        pages = MAX_PAGES,
+        tasks = tasks.len(),
+        "stopped at the page limit, later pages of tasks are missing"
+    );
+    Ok(tasks)
+}
+
+/// This method will fetch a single task from Summit by its id
+///
+/// Summit doesn't expose a per-task endpoint, so this pages through the task
+/// list until the task is found, returning `Error::TaskNotFound` once the last
+/// page, or `MAX_PAGES` pages, have been checked.
+pub async fn get_task(
+    client: &Client,
+    base_url: &str,
+    api: ApiVersion,
+    id: i64,
+) -> Result<Task, Error> {
+    for page in 0..MAX_PAGES {
+        let response = enumerate_tasks(client, base_url, api, page).await?;
+        if let Some(task) = response.items.into_iter().find(|t| t.id == id) {
+            return Ok(task);
+        }
+        if !response.has_next {
+            return Err(Error::TaskNotFound(id));
+        }
+    }
+
+    tracing::warn!(id, pages = MAX_PAGES, "gave up looking for the task");
+    Err(Error::TaskNotFound(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::mock;
+
+    // This function renders a page of the v1 task list without any tasks
+    fn empty_page(page: usize, has_next: bool) -> mock::Response {
+        mock::Response::json(format!(
+            r#"{{"items": [], "numPages": 1000, "page": {page}, "hasPrevious": {}, "hasNext": {has_next}}}"#,
+            page > 0
+        ))
+    }
+
+    // This function renders a page of the v1 task list with the given task ids
+    fn page_of(page: usize, ids: &[i64], has_next: bool) -> mock::Response {
+        let items = ids
+            .iter()
+            .map(|id| {
+                serde_json::json!({
+                    "id": id,
+                    "projectID": 1,
+                    "repoID": 1,
+                    "profileID": 1,
+                    "slug": format!("task-{id}"),
+                    "pkgID": format!("pkg-{id}"),
+                    "architecture": "x86_64",
+                    "buildID": format!("build-{id}"),
+                    "description": "",
+                    "commitRef": "",
+                    "sourcePath": "",
+                    "status": 4,
+                    "tsStarted": 0,
+                    "tsUpdated": 0,
+                    "tsEnded": 0,
+                    "allocatedBuilder": "",
+                    "logPath": "",
+                })
+            })
+            .collect::<Vec<_>>();
+        mock::Response::json(
+            serde_json::json!({
+                "items": items,
+                "numPages": 3,
+                "page": page,
+                "hasPrevious": page > 0,
+                "hasNext": has_next,
+            })
+            .to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn enumerate_all_tasks_walks_every_page() {
+        let server = mock::Server::start(|page, _| {
+            let first = page as i64 * 2;
+            page_of(page, &[first, first + 1], page < 2)
+        })
+        .await;
+
+        let tasks = enumerate_all_tasks(&Client::new(false), &server.url, ApiVersion::V1)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            tasks.iter().map(|t| t.id).collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4, 5]
+        );
+        assert_eq!(
+            server.requests(),
+            [
+                "/api/v1/tasks/enumerate?pageNumber=0",
+                "/api/v1/tasks/enumerate?pageNumber=1",
+                "/api/v1/tasks/enumerate?pageNumber=2",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn enumerate_all_tasks_stops_at_max_pages() {
+        let server = mock::Server::start(|page, _| page_of(page, &[page as i64], true)).await;
+
+        let tasks = enumerate_all_tasks(&Client::new(false), &server.url, ApiVersion::V1)
+            .await
+            .unwrap();
+
+        assert_eq!(tasks.len(), MAX_PAGES as usize);
+        assert_eq!(server.requests().len(), MAX_PAGES as usize);
+    }
+
+    #[tokio::test]
+    async fn get_task_finds_the_task_on_a_later_page() {
+        let server = mock::Server::start(|page, _| {
+            let first = page as i64 * 2;
+            page_of(page, &[first, first + 1], page < 2)
+        })
+        .await;
+
+        let task = get_task(&Client::new(false), &server.url, ApiVersion::V1, 3)
+            .await
+            .unwrap();
+
+        assert_eq!(task.id, 3);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn get_task_stops_at_the_last_page() {
+        let server = mock::Server::start(|page, _| empty_page(page, page < 2)).await;
+
+        let result = get_task(&Client::new(false), &server.url, ApiVersion::V1, 42).await;
+
+        assert!(matches!(result, Err(Error::TaskNotFound(42))));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn get_task_gives_up_after_max_pages() {
+        let server = mock::Server::start(|page, _| empty_page(page, true)).await;
+
+        let result = get_task(&Client::new(false), &server.url, ApiVersion::V1, 42).await;
+
+        assert!(matches!(result, Err(Error::TaskNotFound(42))));
+        assert_eq!(server.requests().len(), MAX_PAGES as usize);
+    }
+}
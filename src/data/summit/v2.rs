@@ -0,0 +1,82 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Types for the v2 Summit API
+//!
+//! The v2 API serves the task enumeration from `/api/v2/tasks/enumerate` and
+//! uses snake_case field names throughout. Responses are converted into the v1
+//! types so that callers only need to deal with a single representation.
+
+use serde::Deserialize;
+
+use super::BuildStatus;
+
+#[derive(Debug, Deserialize)]
+pub struct TaskEnumerateResponse {
+    pub items: Vec<Task>,
+    pub num_pages: i32,
+    pub page: i32,
+    pub has_previous: bool,
+    pub has_next: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Task {
+    pub id: i64,
+    pub project_id: i64,
+    pub repo_id: i64,
+    pub profile_id: i64,
+    pub slug: String,
+    pub pkg_id: String,
+    pub architecture: String,
+    pub build_id: String,
+    pub description: String,
+    pub commit_ref: String,
+    pub source_path: String,
+    pub status: BuildStatus,
+    pub ts_started: i64,
+    pub ts_updated: i64,
+    pub ts_ended: i64,
+    #[serde(default)]
+    pub blocked_by: Vec<String>,
+    pub allocated_builder: String,
+    pub log_path: String,
+}
+
+impl From<TaskEnumerateResponse> for super::TaskEnumerateResponse {
+    fn from(value: TaskEnumerateResponse) -> Self {
+        Self {
+            items: value.items.into_iter().map(Into::into).collect(),
+            num_pages: value.num_pages,
+            page: value.page,
+            has_previous: value.has_previous,
+            has_next: value.has_next,
+        }
+    }
+}
+
+impl From<Task> for super::Task {
+    fn from(value: Task) -> Self {
+        Self {
+            id: value.id,
+            project_id: value.project_id,
+            repo_id: value.repo_id,
+            profile_id: value.profile_id,
+            slug: value.slug,
+            pkg_id: value.pkg_id,
+            architecture: value.architecture,
+            build_id: value.build_id,
+            description: value.description,
+            commit_ref: value.commit_ref,
+            source_path: value.source_path,
+            status: value.status,
+            ts_started: value.ts_started,
+            ts_updated: value.ts_updated,
+            ts_ended: value.ts_ended,
+            blocked_by: value.blocked_by,
+            allocated_builder: value.allocated_builder,
+            log_path: value.log_path,
+        }
+    }
+}
@@ -2,10 +2,23 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use serde::Deserialize;
+use std::{collections::HashMap, time::Duration};
+
+use futures::{Stream, StreamExt};
+
+use reqwest::{
+    header::{HeaderMap, ACCEPT, AUTHORIZATION, USER_AGENT},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{Client, Error};
+
+/// Base URL of the public release-monitoring.org deployment
+pub const RELEASE_MONITORING_URL: &str = "https://release-monitoring.org";
 
 /// Version response from release-monitoring.org
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct VersionResponse {
     pub latest_version: Option<String>,
 
@@ -16,13 +29,407 @@ pub struct VersionResponse {
     pub versions: Vec<String>,
 }
 
+/// How many projects are looked up with a single request
+pub const CHUNK_SIZE: usize = 50;
+
 /// This method will return the latest version of a project
 /// from release-monitoring.org API using the project_id
-pub async fn get_latest_version(project_id: i64) -> Result<VersionResponse, reqwest::Error> {
+///
+/// `base_url` is normally `RELEASE_MONITORING_URL`, but may point at any
+/// deployment of the same API.
+pub async fn get_latest_version(
+    client: &Client,
+    base_url: &str,
+    project_id: i64,
+) -> Result<VersionResponse, Error> {
+    let versions: VersionResponse = get_versions(client, base_url, &project_id.to_string()).await?;
+    tracing::info!(project_id, latest_version = ?versions.latest_version, "fetched versions");
+    Ok(versions)
+}
+
+/// The versions of several projects, as returned for a comma separated `project_id`
+#[derive(Debug, Deserialize)]
+struct BatchResponse {
+    items: Vec<BatchItem>,
+}
+
+/// The versions of a single project within a `BatchResponse`
+#[derive(Debug, Deserialize)]
+struct BatchItem {
+    project_id: i64,
+    #[serde(flatten)]
+    versions: VersionResponse,
+}
+
+/// This method will return the latest versions of up to `CHUNK_SIZE` projects with
+/// a single request, keyed by project id
+///
+/// Projects unknown to release-monitoring.org are missing from the result. A single
+/// project is looked up as by `get_latest_version`.
+pub async fn get_version_chunk(
+    client: &Client,
+    base_url: &str,
+    ids: &[i64],
+) -> Result<HashMap<i64, VersionResponse>, Error> {
+    if let [project_id] = ids {
+        let versions = get_latest_version(client, base_url, *project_id).await?;
+        return Ok(HashMap::from([(*project_id, versions)]));
+    }
+
+    let query = ids.iter().map(i64::to_string).collect::<Vec<_>>().join(",");
+    let batch: BatchResponse = get_versions(client, base_url, &query).await?;
+    tracing::info!(
+        projects = ids.len(),
+        found = batch.items.len(),
+        "fetched versions"
+    );
+    Ok(batch
+        .items
+        .into_iter()
+        .filter(|item| ids.contains(&item.project_id))
+        .map(|item| (item.project_id, item.versions))
+        .collect())
+}
+
+// This function requests the versions endpoint for the given `project_id` query,
+// retrying on rate limits, server errors and unreachable servers
+async fn get_versions<T: serde::de::DeserializeOwned>(
+    client: &Client,
+    base_url: &str,
+    project_ids: &str,
+) -> Result<T, Error> {
     let url = format!(
-        "https://release-monitoring.org/api/v2/versions/?project_id={}",
-        project_id
+        "{}/api/v2/versions/?project_id={}",
+        base_url.trim_end_matches('/'),
+        project_ids
     );
-    let response = reqwest::get(&url).await?.json().await?;
-    Ok(response)
+
+    let retry = client.retry();
+    let mut attempt = 0;
+    loop {
+        tracing::info!(project_ids, %url, attempt, "fetching versions");
+        let response = match client.get(&url)?.send().await {
+            Ok(response) => response,
+            // Unreachable or too slow, which is retried like a failed request
+            Err(e) if (e.is_connect() || e.is_timeout()) && attempt < retry.max_retries => {
+                let wait = retry.delay(attempt);
+                tracing::debug!(project_ids, error = %e, ?wait, "retrying");
+                attempt += 1;
+                tokio::time::sleep(wait).await;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let status = response.status();
+        tracing::trace!(project_ids, %status, headers = ?response.headers(), "response");
+        let retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+        let retry_after = retry_after(response.headers());
+
+        if retryable && attempt < retry.max_retries {
+            // Rejected or failed, so back off and try again
+            let wait = retry_after.unwrap_or_else(|| retry.delay(attempt));
+            tracing::debug!(project_ids, %status, ?wait, "retrying");
+            attempt += 1;
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                client.wait_rate_limit(wait).await;
+            } else {
+                tokio::time::sleep(wait).await;
+            }
+        } else if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(Error::RateLimited(attempt));
+        } else if status.is_success() && remaining(response.headers()) == Some(0) {
+            // Budget exhausted, so hold off before the next request goes out
+            let versions = response.json().await?;
+            if let Some(wait) = retry_after {
+                client.wait_rate_limit(wait).await;
+            }
+            return Ok(versions);
+        } else {
+            return Ok(response.error_for_status()?.json().await?);
+        }
+    }
+}
+
+/// This method will stream the latest versions of many projects as they arrive
+///
+/// The projects are looked up in chunks of `CHUNK_SIZE` with one request each, keeping
+/// up to `jobs` of them in flight. Results are yielded per chunk in order of completion,
+/// so that callers can report progress and stop early without losing what was already
+/// fetched. A failed chunk fails every project within it.
+pub fn stream_latest_versions<'a>(
+    client: &'a Client,
+    base_url: &'a str,
+    ids: &'a [i64],
+    jobs: usize,
+) -> impl Stream<Item = (&'a [i64], Result<HashMap<i64, VersionResponse>, Error>)> + 'a {
+    futures::stream::iter(ids.chunks(CHUNK_SIZE))
+        .map(move |chunk| async move { (chunk, get_version_chunk(client, base_url, chunk).await) })
+        .buffer_unordered(jobs)
+}
+
+/// This method will return the latest versions of many projects, keyed by project id
+///
+/// The projects are looked up as by `stream_latest_versions`. Projects that fail to
+/// fetch are missing from the result, unless network access is disabled, which fails
+/// the whole call.
+pub async fn get_latest_versions(
+    client: &Client,
+    base_url: &str,
+    ids: &[i64],
+    jobs: usize,
+) -> Result<HashMap<i64, VersionResponse>, Error> {
+    let mut versions = HashMap::with_capacity(ids.len());
+    let mut lookups = std::pin::pin!(stream_latest_versions(client, base_url, ids, jobs));
+    while let Some((chunk, result)) = lookups.next().await {
+        match result {
+            Ok(found) => versions.extend(found),
+            Err(Error::NetworkDisabled) => return Err(Error::NetworkDisabled),
+            Err(e) => tracing::info!(projects = ?chunk, error = %e, "failed to fetch versions"),
+        }
+    }
+    Ok(versions)
+}
+
+// This function reads a numeric header from a response
+fn numeric_header(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+}
+
+// This function returns how long the server asked to wait with `Retry-After`
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    numeric_header(headers, "retry-after").map(Duration::from_secs)
+}
+
+// This function returns how many requests remain in the current rate limit window
+fn remaining(headers: &HeaderMap) -> Option<u64> {
+    numeric_header(headers, "x-ratelimit-remaining")
+}
+
+/// The latest release of a GitHub repository
+#[derive(Debug, Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+}
+
+/// This method will return the latest release of a GitHub repository, given as
+/// `owner/repo`, in the same shape as release-monitoring.org versions
+///
+/// GitHub only considers published, non prerelease releases to be the latest, so
+/// it is also reported as the stable version. When the client has a GitHub token
+/// it is used to authenticate, which raises the otherwise tight anonymous rate limit.
+pub async fn get_github_version(client: &Client, repo: &str) -> Result<VersionResponse, Error> {
+    let url = format!("https://api.github.com/repos/{repo}/releases/latest");
+
+    let mut request = client
+        .get(&url)?
+        .header(USER_AGENT, "ent")
+        .header(ACCEPT, "application/vnd.github+json");
+    if let Some(token) = client.github_token() {
+        request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+    }
+
+    tracing::info!(repo, %url, "fetching latest release");
+    let response = request.send().await?;
+    if response.status() == StatusCode::TOO_MANY_REQUESTS
+        || (response.status() == StatusCode::FORBIDDEN && remaining(response.headers()) == Some(0))
+    {
+        return Err(Error::RateLimited(0));
+    }
+    let release: GithubRelease = response.error_for_status()?.json().await?;
+    tracing::info!(repo, tag = %release.tag_name, "fetched latest release");
+
+    Ok(VersionResponse {
+        latest_version: Some(release.tag_name.clone()),
+        stable_versions: vec![release.tag_name.clone()],
+        versions: vec![release.tag_name],
+    })
+}
+
+/// This method will return the URL of a GitHub repository's releases
+pub fn github_url(repo: &str) -> String {
+    format!("https://github.com/{repo}/releases")
+}
+
+/// This method will return the URL of a project's page on release-monitoring.org
+pub fn project_url(project_id: i64) -> String {
+    format!("https://release-monitoring.org/project/{}/", project_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{mock, Retry};
+
+    // This function creates a client retrying quickly, so backoff doesn't slow the tests
+    fn fast_client(max_retries: u32) -> Client {
+        Client::new(false).with_retry(Retry {
+            max_retries,
+            base_delay: Duration::from_millis(10),
+        })
+    }
+
+    // This function answers version requests with `{id}.0` for every project id
+    // asked for, leaving out the ids in `missing`
+    fn versions_server(
+        missing: &'static [i64],
+    ) -> impl Fn(usize, &str) -> mock::Response + Send + Sync + 'static {
+        move |_, target| {
+            let ids = target
+                .rsplit('=')
+                .next()
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|id| id.parse::<i64>().ok())
+                .filter(|id| !missing.contains(id))
+                .collect::<Vec<_>>();
+            match ids.as_slice() {
+                [id] if !target.contains(',') => {
+                    mock::Response::json(format!(r#"{{"latest_version": "{id}.0"}}"#))
+                }
+                [] if !target.contains(',') => mock::Response::status(404),
+                _ => {
+                    let items = ids
+                        .iter()
+                        .map(|id| format!(r#"{{"project_id": {id}, "latest_version": "{id}.0"}}"#))
+                        .collect::<Vec<_>>();
+                    mock::Response::json(format!(r#"{{"items": [{}]}}"#, items.join(",")))
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn get_latest_versions_looks_up_projects_in_chunks() {
+        let server = mock::Server::start(versions_server(&[])).await;
+
+        let ids = (1..=CHUNK_SIZE as i64 + 1).collect::<Vec<i64>>();
+        let versions = get_latest_versions(&Client::new(false), &server.url, &ids, 8)
+            .await
+            .unwrap();
+
+        let mut requests = server.requests();
+        requests.sort();
+        assert_eq!(requests.len(), 2);
+        let first = ids[..CHUNK_SIZE]
+            .iter()
+            .map(i64::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(requests[0], format!("/api/v2/versions/?project_id={first}"));
+        assert_eq!(
+            requests[1],
+            format!("/api/v2/versions/?project_id={}", CHUNK_SIZE + 1)
+        );
+        assert_eq!(versions.len(), ids.len());
+        assert_eq!(versions[&42].latest_version.as_deref(), Some("42.0"));
+    }
+
+    #[tokio::test]
+    async fn get_latest_versions_skips_failed_projects() {
+        let server = mock::Server::start(versions_server(&[2, 5])).await;
+
+        // Unknown projects are left out of a chunk, and fail on their own
+        let versions = get_latest_versions(&Client::new(false), &server.url, &[1, 2, 3], 2)
+            .await
+            .unwrap();
+        let lone = get_latest_versions(&Client::new(false), &server.url, &[5], 2)
+            .await
+            .unwrap();
+
+        let mut found = versions.keys().copied().collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, [1, 3]);
+        assert!(lone.is_empty());
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn get_latest_versions_fails_offline() {
+        let result = get_latest_versions(&Client::new(true), RELEASE_MONITORING_URL, &[1], 1).await;
+        assert!(matches!(result, Err(Error::NetworkDisabled)));
+    }
+
+    #[tokio::test]
+    async fn get_latest_version_honours_retry_after() {
+        let server = mock::Server::start(|index, _| match index {
+            0 => mock::Response::status(429).header("retry-after", 1),
+            _ => mock::Response::json(r#"{"latest_version": "1.0"}"#),
+        })
+        .await;
+
+        // Backing off by the base delay instead would never finish in time
+        let client = Client::new(false).with_retry(Retry {
+            max_retries: 1,
+            base_delay: Duration::from_secs(3600),
+        });
+        let lookup = get_latest_version(&client, &server.url, 1);
+        let lv = tokio::time::timeout(Duration::from_secs(30), lookup)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(lv.latest_version.as_deref(), Some("1.0"));
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(client.rate_limited_for(), Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn get_latest_version_backs_off_on_server_errors() {
+        let server = mock::Server::start(|index, _| {
+            if index < 2 {
+                mock::Response::status(503)
+            } else {
+                mock::Response::json(r#"{"latest_version": "1.0"}"#)
+            }
+        })
+        .await;
+
+        let client = fast_client(2);
+        let lv = get_latest_version(&client, &server.url, 1).await.unwrap();
+
+        assert_eq!(lv.latest_version.as_deref(), Some("1.0"));
+        assert_eq!(server.requests().len(), 3);
+        // Server errors aren't rate limits
+        assert!(client.rate_limited_for().is_zero());
+    }
+
+    #[tokio::test]
+    async fn get_latest_version_gives_up_when_still_rate_limited() {
+        let server = mock::Server::start(|_, _| mock::Response::status(429)).await;
+
+        let result = get_latest_version(&fast_client(2), &server.url, 1).await;
+
+        assert!(matches!(result, Err(Error::RateLimited(2))));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn get_latest_version_retries_unreachable_servers() {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = Client::new(false).with_retry(Retry {
+            max_retries: 3,
+            base_delay: Duration::from_millis(200),
+        });
+        let lookup =
+            tokio::spawn(
+                async move { get_latest_version(&client, &format!("http://{addr}"), 1).await },
+            );
+
+        // Only start listening once the first attempt was refused
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let server = mock::Server::start_at(&addr.to_string(), |_, _| {
+            mock::Response::json(r#"{"latest_version": "1.0"}"#)
+        })
+        .await;
+        let lv = lookup.await.unwrap().unwrap();
+
+        assert_eq!(lv.latest_version.as_deref(), Some("1.0"));
+        assert_eq!(server.requests().len(), 1);
+    }
 }
@@ -2,5 +2,9 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
+pub mod config;
 pub mod data;
 pub mod recipes;
+pub mod state;
+pub mod updates;
+pub mod version;
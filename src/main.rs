@@ -2,368 +2,2170 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashMap,
+    fs,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 
-use clap::{Parser, Subcommand};
+use clap::{
+    error::ErrorKind, parser::ValueSource, ArgAction, ArgMatches, Args, CommandFactory,
+    FromArgMatches, Parser, Subcommand, ValueEnum,
+};
 use colored::Colorize;
 use ent::{
+    config::{self, Config},
     data,
     recipes::{self, ParserRegistration, Recipe, RecipeError},
+    state,
+    updates::{self, RequiredUpdate},
+    version,
 };
 use futures::StreamExt;
 use glob::Pattern;
 use indicatif::ProgressBar;
+use tracing_subscriber::prelude::*;
+
+mod output;
 
 /// A simple CLI tool to check for working with recipe trees
 #[derive(Parser)]
 #[command(name = "cli")]
 struct Cli {
+    #[command(flatten)]
+    global: GlobalArgs,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+impl Cli {
+    /// Whether the exit status reports findings, leaving 2 to signal errors
+    fn exit_code(&self) -> bool {
+        matches!(
+            &self.command,
+            Commands::Check {
+                check_command: CheckCommands::Updates(UpdatesArgs {
+                    exit_code: true,
+                    ..
+                })
+            }
+        )
+    }
+
+    /// Whether network access is refused, by `--no-network` or `check security --offline`
+    fn offline(&self) -> bool {
+        self.global.no_network
+            || matches!(
+                &self.command,
+                Commands::Check {
+                    check_command: CheckCommands::Security(SecurityArgs { offline: true, .. })
+                }
+            )
+    }
+
+    /// Fill in the options left at their built-in defaults from the config file,
+    /// so that flags override the config, which overrides the built-in defaults
+    fn apply_config(&mut self, matches: &ArgMatches, config: &Config) -> Result<(), String> {
+        // Global options are propagated down to the innermost subcommand
+        let mut leaf = matches;
+        while let Some((_, sub)) = leaf.subcommand() {
+            leaf = sub;
+        }
+        let defaulted = |id: &str| leaf.value_source(id) == Some(ValueSource::DefaultValue);
+
+        if let Some(root) = config.root.as_ref().filter(|_| defaulted("root")) {
+            self.global.root = parse_root(&root.to_string_lossy())?;
+        }
+
+        let (jobs, cache_ttl) = match &mut self.command {
+            Commands::Refresh(args) => (Some(&mut args.jobs), None),
+            Commands::Check {
+                check_command: CheckCommands::Updates(args),
+            } => (Some(&mut args.jobs), Some(&mut args.cache_ttl)),
+            _ => (None, None),
+        };
+        if let (Some(jobs), Some(value)) = (jobs, config.jobs) {
+            if defaulted("jobs") {
+                *jobs = parse_jobs(&value.to_string()).map_err(|e| format!("jobs: {e}"))?;
+            }
+        }
+        if let (Some(ttl), Some(value)) = (cache_ttl, &config.cache_ttl) {
+            if defaulted("cache_ttl") {
+                *ttl = data::cache::parse_ttl(value).map_err(|e| format!("cache_ttl: {e}"))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Options shared by all commands
+#[derive(Args)]
+struct GlobalArgs {
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t = Format::Human)]
+    format: Format,
+
+    /// Force a single key convention across all JSON output
+    ///
+    /// Fields emitted by ent are snake_case, while Summit fields passed through
+    /// in the builds JSON keep their upstream camelCase unless this is set.
+    #[arg(long, global = true, value_enum, value_name = "CASE")]
+    json_case: Option<JsonCase>,
+
+    /// Explain the colors used in the output tables
+    #[arg(long, global = true)]
+    legend: bool,
+
+    /// Show more detailed output, and log what ent does to stderr (repeatable)
+    ///
+    /// `-v` logs the requests made and recipes skipped, `-vv` also each recipe
+    /// scanned and `-vvv` everything else.
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Never draw progress bars, which are already hidden when stderr isn't a
+    /// terminal
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Treat malformed and duplicate recipes as errors instead of skipping or
+    /// warning about them
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Omit table headers and the provenance footer from human readable output
+    #[arg(long, global = true)]
+    no_header: bool,
+
+    /// Root of the recipe tree to scan
+    #[arg(long, global = true, value_name = "PATH", default_value = ".", value_parser = parse_root)]
+    root: PathBuf,
+
+    /// Only consider recipes whose path matches this regex (repeatable)
+    #[arg(long, global = true, value_name = "REGEX")]
+    include_paths: Vec<regex::Regex>,
+
+    /// Skip recipes whose path matches this regex, even if included (repeatable)
+    #[arg(long, global = true, value_name = "REGEX")]
+    exclude_paths: Vec<regex::Regex>,
+
+    /// Override the filename patterns of a recipe parser (repeatable)
+    ///
+    /// `stone_recipe=*/stone.{yml,yaml}` replaces the built-in patterns of the
+    /// named parser, while `stone_recipe+=*/stone.yml` adds to them.
+    #[arg(long, global = true, value_name = "PARSER=GLOB")]
+    recipe_glob: Vec<recipes::scan::RecipeGlob>,
+
+    /// Don't descend into directories whose name matches this glob (repeatable),
+    /// in addition to `.git`, `.svn`, `target` and `node_modules`
+    #[arg(long, global = true, value_name = "GLOB")]
+    ignore: Vec<Pattern>,
+
+    /// Also descend into hidden directories, whose name starts with a `.`
+    #[arg(long, global = true)]
+    hidden: bool,
+
+    /// Ellipsize package names longer than N characters in tables
+    /// [default: no limit for updates, 50 for builds]
+    ///
+    /// Machine readable formats always keep the full names.
+    #[arg(long, global = true, value_name = "N", conflicts_with = "no_truncate")]
+    truncate_names: Option<usize>,
+
+    /// Never truncate package names in tables
+    #[arg(long, global = true)]
+    no_truncate: bool,
+
+    /// Also look for monitoring.yaml up to N parent directories above a recipe
+    ///
+    /// A monitoring file next to the recipe always wins, otherwise the nearest
+    /// one found walking up is used.
+    #[arg(long, global = true, value_name = "N", default_value_t = 0)]
+    monitoring_search_depth: usize,
+
+    /// Number of threads used to parse recipes [default: number of CPUs]
+    ///
+    /// Parsing is CPU and disk bound, and is independent of `--jobs`, which
+    /// limits the concurrent network requests of commands that fetch data.
+    #[arg(long, global = true, value_name = "N")]
+    threads: Option<usize>,
+
+    /// Fail any attempted network access instead of performing it
+    #[arg(long, global = true)]
+    no_network: bool,
+
+    /// Base URL of the Summit deployment to talk to
+    #[arg(
+        long,
+        global = true,
+        value_name = "URL",
+        env = "ENT_SUMMIT_BASE_URL",
+        default_value = data::summit::DEFAULT_BASE_URL
+    )]
+    summit_base_url: String,
+
+    /// Summit API version to use (v1 or v2)
+    #[arg(long, global = true, value_name = "VERSION", default_value = "v1")]
+    summit_api_version: data::summit::ApiVersion,
+
+    /// Default version scheme (semver, date or auto), used for recipes that don't
+    /// declare `releases -> scheme` in their monitoring.yaml
+    #[arg(long, global = true, value_name = "SCHEME", default_value = "auto")]
+    assume_version_scheme: version::Scheme,
+}
+
+// This function validates the `--root` argument, so that a bad path is reported
+// cleanly up front rather than failing part way through the scan
+fn parse_root(s: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(s);
+    match fs::metadata(&path) {
+        Ok(metadata) if metadata.is_dir() => Ok(path),
+        Ok(_) => Err(format!("`{s}` is not a directory")),
+        Err(e) => Err(format!("`{s}` is not accessible: {e}")),
+    }
+}
+
+// This function validates `--jobs`, as no requests would ever be made with zero
+fn parse_jobs(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("at least one job is required, use 1 for sequential requests".to_string()),
+        Ok(jobs) => Ok(jobs),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Output formats supported by the CLI
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Human readable, colored output
+    Human,
+    /// Machine readable JSON output
+    Json,
+    /// JUnit XML test report
+    Junit,
+    /// Prometheus text exposition format, for the node-exporter textfile collector
+    ///
+    /// `check updates` emits `ent_outdated_packages`, `ent_check_errors` and
+    /// `ent_package_outdated{name="..."}`, while `builds` emits
+    /// `ent_builds{status="..."}`.
+    Prometheus,
+    /// RFC 4180 CSV with a header row, for spreadsheets
+    ///
+    /// `check updates` emits `source,current_version,latest_version`.
+    Csv,
+}
+
+/// Key conventions for JSON output
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum JsonCase {
+    /// snake_case keys, e.g. `project_id`
+    Snake,
+    /// camelCase keys, e.g. `projectId`
+    Camel,
+}
+
+impl JsonCase {
+    // This function converts a single key to the convention
+    fn convert(self, key: &str) -> String {
+        let mut out = String::with_capacity(key.len());
+        match self {
+            JsonCase::Snake => {
+                let mut prev_lower = false;
+                for c in key.chars() {
+                    if c.is_ascii_uppercase() {
+                        if prev_lower {
+                            out.push('_');
+                        }
+                        out.push(c.to_ascii_lowercase());
+                        prev_lower = false;
+                    } else {
+                        out.push(c);
+                        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+                    }
+                }
+            }
+            JsonCase::Camel => {
+                let mut upper = false;
+                for c in key.chars() {
+                    if c == '_' && !out.is_empty() {
+                        upper = true;
+                    } else if upper {
+                        out.push(c.to_ascii_uppercase());
+                        upper = false;
+                    } else {
+                        out.push(c);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    // This function converts every object key in the value, recursively
+    fn apply(self, value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => map
+                .into_iter()
+                .map(|(k, v)| (self.convert(&k), self.apply(v)))
+                .collect(),
+            serde_json::Value::Array(items) => items.into_iter().map(|v| self.apply(v)).collect(),
+            other => other,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Refresh the cache
-    Refresh,
+    Refresh(RefreshArgs),
     /// Check for updates or security status
     Check {
         #[command(subcommand)]
         check_command: CheckCommands,
     },
     /// List recent builds from Summit
-    Builds,
+    Builds(BuildsArgs),
+    /// Parse a single recipe and print what ent sees in it
+    Parse(ParseArgs),
+    /// Audit the recipe tree
+    Audit {
+        #[command(subcommand)]
+        audit_command: AuditCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuditCommands {
+    /// List packages by license, flagging missing or non-SPDX licenses
+    Licenses,
+}
+
+/// Options for `refresh`
+#[derive(Args)]
+struct RefreshArgs {
+    /// Drop cache entries for recipes that no longer exist in the tree
+    #[arg(long)]
+    prune: bool,
+
+    /// Maximum number of concurrent network requests, independent of `--threads`
+    #[arg(long, value_name = "N", default_value_t = 16, value_parser = parse_jobs)]
+    jobs: usize,
+}
+
+/// Options for `parse`
+#[derive(Args)]
+struct ParseArgs {
+    /// Recipe file to parse, or `-` to read it from stdin
+    path: PathBuf,
+
+    /// Parser to use, e.g. `stone` or `ypkg`. Required when reading from stdin,
+    /// otherwise chosen by the filename.
+    #[arg(long = "type", value_name = "TYPE")]
+    recipe_type: Option<RecipeType>,
+}
+
+/// Options for `builds`
+#[derive(Args)]
+struct BuildsArgs {
+    /// Only show the task with the given id
+    ///
+    /// The task list is only paged through until the task is found.
+    #[arg(long, value_name = "ID")]
+    task: Option<i64>,
+
+    /// Only show builds for the given Summit project id
+    #[arg(long, value_name = "ID")]
+    project: Option<i64>,
+
+    /// Only show builds with the given status, may be repeated
+    #[arg(long, value_name = "STATUS")]
+    status: Vec<data::summit::BuildStatus>,
+
+    /// Only show builds for the given architecture, such as `x86_64`
+    #[arg(long, value_name = "NAME")]
+    arch: Option<String>,
+
+    /// Show a link to the build log of each task
+    #[arg(long)]
+    with_log: bool,
+
+    /// Order of the builds within each status group
+    #[arg(long, value_enum, default_value_t = BuildSort::Updated)]
+    sort: BuildSort,
+
+    /// Show the number of builds in each status per architecture, rather than
+    /// the builds themselves
+    #[arg(long)]
+    summary: bool,
+
+    /// Keep redrawing the builds until interrupted with Ctrl-C
+    #[arg(long)]
+    watch: bool,
+
+    /// Seconds between refreshes with --watch
+    #[arg(long, value_name = "SECS", default_value_t = 30, requires = "watch")]
+    interval: u64,
+}
+
+/// Orderings for the builds table
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum BuildSort {
+    /// Most recently updated first
+    Updated,
+    /// Highest task id first
+    Id,
 }
 
 #[derive(Subcommand)]
 enum CheckCommands {
     /// Check for updates
-    Updates,
+    Updates(UpdatesArgs),
     /// Check for security status
-    Security,
+    Security(SecurityArgs),
 }
 
-// This function scans the directory for recipes and parses them
-fn scan_dir(
-    root: impl AsRef<Path>,
-    globs: &HashMap<Pattern, &&ParserRegistration>,
-) -> Result<Vec<recipes::Recipe>, recipes::RecipeError> {
-    let root = root.as_ref();
-    let mut ret = vec![];
-
-    for entry in root.read_dir()?.flatten() {
-        let path = entry.path();
-        if path.is_dir() {
-            ret.extend(scan_dir(&path, globs)?);
-        } else {
-            for (pattern, parser) in globs {
-                if pattern.matches_path(&path) {
-                    let parser = (parser.parser)();
-                    let r = parser.parse(&path)?;
-                    ret.push(r);
+/// Options for `check security`
+#[derive(Args)]
+struct SecurityArgs {
+    /// Look up the CVEs affecting a single CPE 2.3 name instead of scanning recipes
+    #[arg(long, value_name = "CPE")]
+    cpe: Option<data::nvd::Cpe>,
+
+    /// NVD CVE JSON feed to match the recipes' `security -> cpe` entries against,
+    /// either a legacy 1.1 feed or a saved 2.0 API response. A directory loads
+    /// every `*.json` feed within it.
+    #[arg(long, value_name = "PATH", required_unless_present = "cpe")]
+    feed: Option<PathBuf>,
+
+    /// Only match against the local feed, refusing any network access like
+    /// `--no-network`
+    #[arg(long, requires = "feed", conflicts_with = "cpe")]
+    offline: bool,
+
+    /// Only report CVEs at least this severe: none, low, medium, high or critical.
+    /// CVEs without CVSS metrics are only reported at `none`.
+    #[arg(long, value_name = "SEVERITY", default_value = "low")]
+    min_severity: data::nvd::Severity,
+}
+
+/// Options for `check updates`
+#[derive(Args)]
+struct UpdatesArgs {
+    /// Release channel to select upstream versions from (latest or stable)
+    #[arg(long, default_value = "latest")]
+    channel: updates::Channel,
+
+    /// Only consider the versions upstream marks as stable, reporting recipes
+    /// without any as having no stable release instead of falling back to the
+    /// latest version. Can also be set per recipe with `releases -> stable_only`.
+    #[arg(long)]
+    stable_only: bool,
+
+    /// Compare against a baseline catalog saved by a previous run
+    #[arg(long, value_name = "CATALOG")]
+    baseline: Option<PathBuf>,
+
+    /// Only check the newest version of recipes sharing a normalized name,
+    /// e.g. `python3.12` rather than `python3.11`
+    #[arg(long)]
+    select_newest_per_source: bool,
+
+    /// Also report upstream source URLs that no longer exist
+    #[arg(long)]
+    check_sources: bool,
+
+    /// Maximum number of concurrent network requests, independent of `--threads`
+    #[arg(long, value_name = "N", default_value_t = 32, value_parser = parse_jobs)]
+    jobs: usize,
+
+    /// Save the results as a baseline catalog for later comparison
+    #[arg(long, value_name = "CATALOG")]
+    save_baseline: Option<PathBuf>,
+
+    /// Rewrite the version line of outdated recipes to the suggested upstream version
+    ///
+    /// Only the version value is touched, and only when it is a plain version
+    /// string matching the parsed recipe. Nothing else in the recipe is updated.
+    #[arg(long)]
+    write_suggestions: bool,
+
+    /// Show the edits `--write-suggestions` would make without writing them
+    #[arg(long, requires = "write_suggestions")]
+    dry_run: bool,
+
+    /// Check the single recipe read from stdin instead of scanning the tree
+    #[arg(long, requires = "recipe_type", conflicts_with = "write_suggestions")]
+    stdin: bool,
+
+    /// Parser to read the recipe on stdin with, e.g. `stone` or `ypkg`
+    #[arg(long = "type", value_name = "TYPE")]
+    recipe_type: Option<RecipeType>,
+
+    /// Use versions cached by `ent refresh` when younger than this, e.g. `6h` or `30m`
+    ///
+    /// Projects missing from the cache or with older entries are fetched and
+    /// written back to the cache. A TTL of `0` always fetches.
+    #[arg(long, value_name = "TTL", default_value = "6h", value_parser = data::cache::parse_ttl)]
+    cache_ttl: std::time::Duration,
+
+    /// Only verify that every monitoring project id resolves to a release-monitoring.org
+    /// project, without comparing versions
+    #[arg(long)]
+    validate_ids: bool,
+
+    /// Only check recipes whose name matches this glob, such as `python-*` (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    filter: Vec<Pattern>,
+
+    /// Exit with status 1 when updates are available, and 2 on errors, including
+    /// upstreams that failed to be looked up, for gating CI
+    #[arg(long)]
+    exit_code: bool,
+
+    /// Print an update debt score summarizing how far behind the tree is
+    #[arg(long)]
+    score: bool,
+
+    /// Weights of major, minor and patch bumps in the update debt score
+    ///
+    /// Outdated packages whose bump can't be determined count as 1.
+    #[arg(long, value_name = "MAJOR,MINOR,PATCH", default_value = "3,2,1")]
+    score_weights: ScoreWeights,
+}
+
+/// Weights of each kind of version bump in the update debt score
+#[derive(Clone, Copy, Debug)]
+struct ScoreWeights {
+    major: u64,
+    minor: u64,
+    patch: u64,
+}
+
+impl ScoreWeights {
+    // This function weighs a single outdated package by its bump
+    fn weight(&self, bump: Option<version::Bump>) -> u64 {
+        match bump {
+            Some(version::Bump::Major) => self.major,
+            Some(version::Bump::Minor) => self.minor,
+            Some(version::Bump::Patch) => self.patch,
+            None => 1,
+        }
+    }
+}
+
+impl FromStr for ScoreWeights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let weights = s
+            .split(',')
+            .map(|w| w.trim().parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid weight in `{s}`: {e}"))?;
+        match weights[..] {
+            [major, minor, patch] => Ok(Self {
+                major,
+                minor,
+                patch,
+            }),
+            _ => Err(format!("expected MAJOR,MINOR,PATCH weights, got `{s}`")),
+        }
+    }
+}
+
+/// Errors surfaced by the CLI
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error("{0}")]
+    Recipe(#[from] RecipeError),
+
+    #[error("{0}")]
+    Data(#[from] data::Error),
+
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("{0}")]
+    Config(#[from] config::Error),
+
+    #[error("Interrupted")]
+    Interrupted,
+
+    #[error("Failed to look up {failed} upstreams, {rate_limited} of them still rate limited")]
+    LookupFailed { failed: usize, rate_limited: usize },
+}
+
+impl Error {
+    /// A stable, machine friendly classification of the error
+    fn kind(&self) -> &'static str {
+        match self {
+            Error::Recipe(RecipeError::InvalidRecipe(_))
+            | Error::Recipe(RecipeError::InvalidMonitoring(..)) => "parse",
+            Error::Recipe(RecipeError::UnsupportedRecipe) => "unsupported",
+            Error::Recipe(RecipeError::EmptyName(_))
+            | Error::Recipe(RecipeError::EmptyVersion(_))
+            | Error::Recipe(RecipeError::DuplicateName(..)) => "invalid",
+            Error::Recipe(RecipeError::IO(e)) | Error::IO(e) => {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    "not_found"
+                } else {
+                    "io"
+                }
+            }
+            Error::Data(data::Error::NetworkDisabled) => "network_disabled",
+            Error::Data(data::Error::TaskNotFound(_))
+            | Error::Data(data::Error::ProjectNotFound(_)) => "not_found",
+            Error::Data(data::Error::RateLimited(_)) => "rate_limited",
+            Error::Data(data::Error::Http(e)) => {
+                if e.status() == Some(reqwest::StatusCode::NOT_FOUND) {
+                    "not_found"
+                } else if e.is_decode() {
+                    "parse"
+                } else {
+                    "network"
                 }
             }
+            Error::Json(_) => "parse",
+            Error::Csv(_) => "io",
+            Error::Config(config::Error::IO(..)) => "io",
+            Error::Config(config::Error::Invalid(..)) => "config",
+            Error::Interrupted => "interrupted",
+            Error::LookupFailed { .. } => "lookup_failed",
         }
     }
 
-    Ok(ret)
+    /// Additional structured details about the error
+    fn context(&self) -> serde_json::Value {
+        match self {
+            Error::Recipe(RecipeError::InvalidRecipe(path))
+            | Error::Recipe(RecipeError::EmptyName(path))
+            | Error::Recipe(RecipeError::EmptyVersion(path)) => serde_json::json!({ "path": path }),
+            Error::Recipe(RecipeError::IO(e)) | Error::IO(e) => {
+                serde_json::json!({ "io_kind": format!("{:?}", e.kind()) })
+            }
+            Error::Recipe(RecipeError::DuplicateName(name, paths)) => {
+                serde_json::json!({ "name": name, "paths": paths })
+            }
+            Error::Data(data::Error::TaskNotFound(id)) => serde_json::json!({ "task_id": id }),
+            Error::Data(data::Error::ProjectNotFound(id)) => {
+                serde_json::json!({ "project_id": id })
+            }
+            Error::Data(data::Error::RateLimited(retries)) => {
+                serde_json::json!({ "retries": retries })
+            }
+            Error::LookupFailed {
+                failed,
+                rate_limited,
+            } => serde_json::json!({ "failed": failed, "rate_limited": rate_limited }),
+            Error::Data(data::Error::Http(e)) => serde_json::json!({
+                "url": e.url().map(|u| u.as_str()),
+                "status": e.status().map(|s| s.as_u16()),
+            }),
+            _ => serde_json::json!({}),
+        }
+    }
 }
 
-// This function scans the recipes in the current directory
-fn scan_recipes(root: impl AsRef<Path>) -> Result<Vec<Recipe>, RecipeError> {
-    let registry = inventory::iter::<ParserRegistration>
-        .into_iter()
-        .map(|p| (p.name, p))
-        .collect::<HashMap<_, _>>();
+// This function reports an error to stderr in the requested format
+fn report_error(error: &Error, global: &GlobalArgs) {
+    match global.format {
+        Format::Human | Format::Junit | Format::Prometheus | Format::Csv => {
+            eprintln!("{} {}", "Error:".red().bold(), error)
+        }
+        Format::Json => {
+            let report = serde_json::json!({
+                "error": {
+                    "kind": error.kind(),
+                    "message": error.to_string(),
+                    "context": error.context(),
+                }
+            });
+            eprintln!("{}", output::json_output(global, report));
+        }
+    }
+}
 
-    let glob_patterns = registry
-        .values()
-        .flat_map(|p| {
-            p.pattern
-                .iter()
-                .map(move |&s| (Pattern::new(s).unwrap(), p))
-        })
-        .collect::<HashMap<_, _>>();
+/// A recipe parser selected with `--type`
+#[derive(Clone, Copy, Debug)]
+struct RecipeType(&'static ParserRegistration);
+
+impl FromStr for RecipeType {
+    type Err = String;
 
-    let scanned = scan_dir(root, &glob_patterns)?;
-    Ok(scanned)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        recipes::scan::find_parser(s).map(RecipeType)
+    }
 }
 
-/// A required update for CLI rendering
-#[derive(Debug)]
-pub struct RequiredUpdate {
-    pub source: String,
-    pub current_version: String,
-    pub latest_version: String,
+// This function returns the recipe parsing options selected on the command line
+fn parse_options(global: &GlobalArgs) -> recipes::ParseOptions {
+    recipes::ParseOptions {
+        monitoring_depth: global.monitoring_search_depth,
+    }
 }
 
-/// Checks for available updates by comparing local recipe versions with upstream versions
-/// Returns a formatted display of packages that need updating
-async fn check_updates(root: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
-    // Scan local recipes
-    let recipes = scan_recipes(root)?;
+// This function parses a single recipe from stdin with the given parser. As
+// there is no recipe file, monitoring data is looked for in the current directory.
+fn parse_stdin(recipe_type: RecipeType, global: &GlobalArgs) -> Result<Recipe, Error> {
+    let contents = recipes::normalize_line_endings(std::io::read_to_string(std::io::stdin())?);
+    let path = Path::new("-");
+    let parser = (recipe_type.0.parser)();
+    Ok(parser
+        .parse_contents(&contents, path, &parse_options(global))
+        .and_then(|r| r.validate(path))?)
+}
 
-    // Setup progress bar for async operations
-    let pb = ProgressBar::new(recipes.len() as u64);
+// This function parses a single recipe file, or stdin when the path is `-`. Unless
+// the type is given, the parser is chosen by matching the path against the
+// registered patterns.
+fn parse_recipe(
+    path: &Path,
+    recipe_type: Option<RecipeType>,
+    global: &GlobalArgs,
+) -> Result<Recipe, Error> {
+    if path == Path::new("-") {
+        return match recipe_type {
+            Some(recipe_type) => parse_stdin(recipe_type, global),
+            None => Err(RecipeError::UnsupportedRecipe.into()),
+        };
+    }
+
+    let registration = match recipe_type {
+        Some(RecipeType(registration)) => registration,
+        None => inventory::iter::<ParserRegistration>
+            .into_iter()
+            .find(|p| {
+                p.pattern
+                    .iter()
+                    .flat_map(|s| recipes::scan::expand_braces(s))
+                    .filter_map(|s| Pattern::new(&s).ok())
+                    .any(|pattern| pattern.matches_path(path))
+            })
+            .ok_or(RecipeError::UnsupportedRecipe)?,
+    };
+    let parser = (registration.parser)();
+    Ok(parser
+        .parse(path, &parse_options(global))
+        .and_then(|r| r.validate(path))?)
+}
+
+// This function prints a single parsed recipe
+fn print_recipe(global: &GlobalArgs, recipe: &Recipe) -> Result<(), Error> {
+    let project_id = recipe.monitoring.as_ref().map(|m| m.project_id);
+
+    if global.format == Format::Json {
+        let recipe = serde_json::json!({
+            "name": recipe.name,
+            "version": recipe.version,
+            "path": recipe.path,
+            "project_id": project_id,
+            "licenses": recipe.licenses,
+            "homepage": recipe.homepage,
+            "source_urls": recipe.source_urls,
+        });
+        return output::print_json(global, recipe);
+    }
+
+    println!("{:<12} {}", "Name".bold(), recipe.name.cyan());
+    println!("{:<12} {}", "Version".bold(), recipe.version);
+    println!("{:<12} {}", "Path".bold(), recipe.path.display());
+    println!(
+        "{:<12} {}",
+        "Project".bold(),
+        project_id.map_or_else(|| "-".to_string(), |id| id.to_string())
+    );
+    println!("{:<12} {}", "Licenses".bold(), recipe.licenses.join(", "));
+    println!(
+        "{:<12} {}",
+        "Homepage".bold(),
+        recipe.homepage.as_deref().unwrap_or("-")
+    );
+    for url in &recipe.source_urls {
+        println!("{:<12} {}", "Source".bold(), url);
+    }
+
+    Ok(())
+}
+
+// This function returns the recipe scanning options selected on the command line
+fn scan_options(global: &GlobalArgs) -> recipes::scan::ScanOptions {
+    recipes::scan::ScanOptions {
+        parse: parse_options(global),
+        recipe_globs: global.recipe_glob.clone(),
+        ignore: global.ignore.clone(),
+        hidden: global.hidden,
+        include_paths: global.include_paths.clone(),
+        exclude_paths: global.exclude_paths.clone(),
+        strict: global.strict,
+    }
+}
+
+// This function scans the recipes under the root of the tree. The recipes
+// skipped and the names defined more than once are logged as warnings.
+fn scan_recipes(root: impl AsRef<Path>, global: &GlobalArgs) -> Result<Vec<Recipe>, RecipeError> {
+    Ok(recipes::scan::scan(root, &scan_options(global))?.recipes)
+}
+
+// This function keeps only the highest versioned recipe of each logical source
+fn newest_per_source(recipes: Vec<Recipe>, default_scheme: version::Scheme) -> Vec<Recipe> {
+    let mut newest = HashMap::<String, Recipe>::new();
+    for recipe in recipes {
+        let scheme = recipe
+            .monitoring
+            .as_ref()
+            .and_then(|m| m.scheme)
+            .unwrap_or(default_scheme);
+        let key = recipes::normalized_name(&recipe.name);
+        let superseded = newest
+            .get(&key)
+            .is_some_and(|existing| scheme.compare(&existing.version, &recipe.version).is_ge());
+        if !superseded {
+            newest.insert(key, recipe);
+        }
+    }
+    newest.into_values().collect()
+}
+
+// This function creates the progress bar used for long running async operations
+fn progress_bar(global: &GlobalArgs, len: usize) -> ProgressBar {
+    // Keep machine readable output, and output captured to logs or pipes, free
+    // of terminal noise. Logs would be torn up by redraws, so they win too.
+    if global.format != Format::Human
+        || global.quiet
+        || global.verbose > 0
+        || !std::io::stderr().is_terminal()
+    {
+        return ProgressBar::hidden();
+    }
+
+    let pb = ProgressBar::new(len as u64);
     pb.set_style(
         indicatif::ProgressStyle::default_bar()
             .template("{spinner:.green} [{bar:40.cyan/blue}] {pos}/{len} ({eta}) : {msg:.bold}")
             .unwrap()
             .progress_chars("#>-"),
     );
+    pb
+}
+
+/// An upstream source URL that no longer resolves
+struct DeadSource {
+    source: String,
+    url: String,
+    status: data::sources::SourceStatus,
+}
+
+// This function probes the upstream source URLs of all recipes concurrently,
+// returning those that are dead
+async fn check_sources(
+    client: &data::Client,
+    global: &GlobalArgs,
+    recipes: &[Recipe],
+    jobs: usize,
+) -> Result<Vec<DeadSource>, Error> {
+    let urls = recipes
+        .iter()
+        .flat_map(|r| {
+            r.source_urls
+                .iter()
+                .filter(|u| u.starts_with("http://") || u.starts_with("https://"))
+                .map(move |u| (r, u))
+        })
+        .collect::<Vec<_>>();
 
-    // Process recipes concurrently to check for updates
-    let futures = futures::stream::iter(recipes)
-        .map(|recipe| {
+    let pb = progress_bar(global, urls.len());
+    let results = futures::stream::iter(urls)
+        .map(|(recipe, url)| {
             let pb = pb.clone();
             async move {
                 pb.set_message(recipe.name.to_string());
+                let status = data::sources::probe(client, url).await;
+                pb.inc(1);
+                status.map(|status| (recipe, url, status))
+            }
+        })
+        .buffer_unordered(jobs)
+        .collect::<Vec<_>>()
+        .await;
+    pb.finish_and_clear();
 
-                // Check if recipe has monitoring info and get latest version
-                let latest_version = if let Some(m) = &recipe.monitoring {
-                    if m.project_id != 0 {
-                        let lv = data::updates::get_latest_version(m.project_id).await?;
-                        // Determine next version - prefer stable > latest > first available
-                        let next_version = if let Some(stable) = lv.stable_versions.first().cloned()
-                        {
-                            Some(stable)
-                        } else if let Some(latest) = lv.latest_version {
-                            Some(latest.clone())
-                        } else {
-                            lv.versions.first().cloned()
-                        };
-
-                        // Create update info if versions differ
-                        if let Some(nv) = next_version {
-                            if nv != recipe.version {
-                                Some(RequiredUpdate {
-                                    source: recipe.name.clone(),
-                                    current_version: recipe.version.clone(),
-                                    latest_version: nv,
-                                })
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
+    let mut dead = vec![];
+    for result in results {
+        let (recipe, url, status) = result?;
+        if status.is_dead() {
+            dead.push(DeadSource {
+                source: recipe.name.clone(),
+                url: url.clone(),
+                status,
+            });
+        }
+    }
+    dead.sort_by(|a, b| a.source.cmp(&b.source).then_with(|| a.url.cmp(&b.url)));
+
+    Ok(dead)
+}
+
+/// A monitoring project id that doesn't resolve to a usable project
+struct InvalidId {
+    project_id: i64,
+    sources: Vec<String>,
+    reason: &'static str,
+}
 
+// This function fetches each distinct project id once, collecting those that
+// don't exist or have no versions at all
+async fn validate_ids(
+    client: &data::Client,
+    projects: &HashMap<i64, Vec<&Recipe>>,
+    pb: &ProgressBar,
+    jobs: usize,
+) -> Result<Vec<InvalidId>, Error> {
+    let results = futures::stream::iter(projects)
+        .map(|(&project_id, members)| {
+            let pb = pb.clone();
+            async move {
+                pb.set_message(members[0].name.to_string());
+                let lv = data::updates::get_latest_version(
+                    client,
+                    data::updates::RELEASE_MONITORING_URL,
+                    project_id,
+                )
+                .await;
                 pb.inc(1);
-                Ok(latest_version) as Result<Option<RequiredUpdate>, Box<dyn std::error::Error>>
+                (project_id, members, lv)
             }
         })
-        .buffer_unordered(32); // Process up to 32 concurrent requests
+        .buffer_unordered(jobs)
+        .collect::<Vec<_>>()
+        .await;
+    pb.finish_and_clear();
+
+    let mut invalid = vec![];
+    for (project_id, members, lv) in results {
+        let reason = match lv {
+            Ok(lv) if lv.latest_version.is_none() && lv.versions.is_empty() => "no versions",
+            Ok(_) => continue,
+            Err(data::Error::Http(e)) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                "not found"
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut sources = members.iter().map(|r| r.name.clone()).collect::<Vec<_>>();
+        sources.sort();
+        invalid.push(InvalidId {
+            project_id,
+            sources,
+            reason,
+        });
+    }
+    invalid.sort_by_key(|i| i.project_id);
+
+    Ok(invalid)
+}
+
+// This function prints the monitoring ids found invalid by `--validate-ids`
+fn print_invalid_ids(global: &GlobalArgs, invalid: &[InvalidId], checked: usize) {
+    println!(
+        "\nInvalid monitoring ids: {}\n",
+        invalid.len().to_string().red()
+    );
+    for i in invalid {
+        println!(
+            "{} {} {}",
+            i.project_id.to_string().yellow(),
+            i.reason.red(),
+            i.sources.join(", ").cyan()
+        );
+    }
+
+    output::print_footer(global, checked, "project ids");
+}
+
+/// A snapshot of the update situation of a recipe tree
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Catalog {
+    packages: Vec<CatalogEntry>,
+}
+
+/// A single recipe within a catalog
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CatalogEntry {
+    source: String,
+    current_version: String,
+    /// The newer upstream version, only present when the recipe is outdated
+    latest_version: Option<String>,
+}
+
+/// Changes since a baseline catalog
+#[derive(Debug, Default)]
+struct BaselineDiff {
+    /// Recipes that are outdated now but weren't in the baseline
+    newly_outdated: Vec<String>,
+    /// Recipes that have caught up with the upstream version known in the baseline
+    updated: Vec<String>,
+    /// Recipes that didn't exist in the baseline
+    added: Vec<String>,
+}
+
+impl Catalog {
+    /// Load a catalog from disk
+    fn load(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Save the catalog to disk
+    fn save(&self, path: &Path) -> Result<(), Error> {
+        state::write_atomic(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Compute the changes from a baseline catalog to this one, comparing versions
+    /// with the scheme of each source
+    fn diff(&self, baseline: &Catalog, scheme: impl Fn(&str) -> version::Scheme) -> BaselineDiff {
+        let previous = baseline
+            .packages
+            .iter()
+            .map(|p| (p.source.as_str(), p))
+            .collect::<HashMap<_, _>>();
+
+        let mut diff = BaselineDiff::default();
+        for package in &self.packages {
+            let Some(old) = previous.get(package.source.as_str()) else {
+                diff.added.push(package.source.clone());
+                continue;
+            };
+
+            match (&old.latest_version, &package.latest_version) {
+                (None, Some(_)) => diff.newly_outdated.push(package.source.clone()),
+                (Some(target), _)
+                    if scheme(&package.source)
+                        .compare(&package.current_version, target)
+                        .is_ge() =>
+                {
+                    diff.updated.push(package.source.clone())
+                }
+                _ => {}
+            }
+        }
+
+        diff
+    }
+}
+
+/// Checks for available updates by comparing local recipe versions with upstream versions
+/// Prints the packages that need updating, and returns how many there are
+///
+/// Versions are compared using the scheme declared by the recipe's monitoring.yaml,
+/// falling back to `default_scheme` from the command line, which itself defaults to
+/// guessing the scheme from the versions involved.
+async fn check_updates(
+    client: &data::Client,
+    root: impl AsRef<Path>,
+    global: &GlobalArgs,
+    args: &UpdatesArgs,
+) -> Result<usize, Error> {
+    let default_scheme = global.assume_version_scheme;
 
-    // Collect results
-    let latest_recipes: Vec<_> = futures.collect().await;
+    // Scan local recipes, or take the single recipe given on stdin
+    let mut recipes = match args.recipe_type.filter(|_| args.stdin) {
+        Some(recipe_type) => vec![parse_stdin(recipe_type, global)?],
+        None => scan_recipes(root, global)?,
+    };
+    if !args.filter.is_empty() {
+        recipes.retain(|r| args.filter.iter().any(|p| p.matches(&r.name)));
+    }
+    if args.select_newest_per_source {
+        recipes = newest_per_source(recipes, default_scheme);
+    }
+
+    // Setup progress bar for async operations
+    let pb = progress_bar(global, 0);
+
+    // Surface rate limiting in the progress bar rather than silently stalling
+    let client = &client.clone().on_rate_limit({
+        let pb = pb.clone();
+        move |wait| pb.set_message(format!("(rate limited, waiting {}s)", wait.as_secs()))
+    });
+
+    // Only verify that the monitoring ids resolve, skipping the version comparison
+    if args.validate_ids {
+        let projects = updates::projects(&recipes);
+        pb.set_length(projects.len() as u64);
+        let invalid = validate_ids(client, &projects, &pb, args.jobs).await?;
+        print_invalid_ids(global, &invalid, projects.len());
+        return Ok(0);
+    }
+
+    // Look up the upstream versions, serving fresh projects from the version cache.
+    // On Ctrl-C stop early so the updates found so far can still be reported.
+    let cache_path = data::cache::VersionCache::default_path();
+    let cache = Arc::new(Mutex::new(
+        cache_path
+            .as_deref()
+            .map(load_version_cache)
+            .unwrap_or_default(),
+    ));
+    let interrupt = Arc::new(tokio::sync::Notify::new());
+    let on_ctrl_c = tokio::spawn({
+        let interrupt = interrupt.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                interrupt.notify_one();
+            }
+        }
+    });
+    let options = updates::Options {
+        channel: args.channel,
+        stable_only: args.stable_only,
+        default_scheme,
+        jobs: args.jobs,
+        cache_ttl: args.cache_ttl,
+        ..Default::default()
+    }
+    .with_cache(cache.clone())
+    .with_interrupt(interrupt)
+    .on_progress({
+        let pb = pb.clone();
+        move |done, total| {
+            pb.set_length(total);
+            pb.set_position(done);
+        }
+    });
+    let report = updates::find_updates(client, &recipes, &options).await;
+    on_ctrl_c.abort();
     pb.finish_and_clear();
+    let updates::Report {
+        updates,
+        no_stable,
+        held_back,
+        downgrades,
+        latest_versions,
+        github_versions,
+        failures,
+        fetched,
+        interrupted,
+    } = report?;
+
+    // Keep the cache warm for the next run, which is best effort
+    if let Some(path) = cache_path.filter(|_| fetched > 0) {
+        if let Err(e) = cache.lock().unwrap().save(&path) {
+            eprintln!(
+                "{} unable to write cache {}: {}",
+                "Warning:".yellow().bold(),
+                path.display(),
+                e
+            );
+        }
+    }
+    let update_count = updates.len();
+
+    // Probe upstream sources if requested
+    let dead_sources = if args.check_sources && !interrupted {
+        Some(check_sources(client, global, &recipes, args.jobs).await?)
+    } else {
+        None
+    };
+
+    // Build the catalog of this run for baseline handling
+    let outdated = updates
+        .iter()
+        .map(|u| (u.source.as_str(), u.latest_version.as_str()))
+        .collect::<HashMap<_, _>>();
+    let catalog = Catalog {
+        packages: recipes
+            .iter()
+            .map(|r| CatalogEntry {
+                source: r.name.clone(),
+                current_version: r.version.clone(),
+                latest_version: outdated.get(r.name.as_str()).map(|v| v.to_string()),
+            })
+            .collect(),
+    };
+    let baseline_diff = match &args.baseline {
+        Some(path) => {
+            let schemes = recipes
+                .iter()
+                .map(|r| {
+                    let scheme = r.monitoring.as_ref().and_then(|m| m.scheme);
+                    (r.name.as_str(), scheme.unwrap_or(default_scheme))
+                })
+                .collect::<HashMap<_, _>>();
+            let scheme = |source: &str| schemes.get(source).copied().unwrap_or(default_scheme);
+            Some(catalog.diff(&Catalog::load(path)?, scheme))
+        }
+        None => None,
+    };
+    // Weigh each outdated package by the size of its version bump
+    let debt_score = args.score.then(|| {
+        let schemes = recipes
+            .iter()
+            .map(|r| {
+                let scheme = r.monitoring.as_ref().and_then(|m| m.scheme);
+                (r.name.as_str(), scheme.unwrap_or(default_scheme))
+            })
+            .collect::<HashMap<_, _>>();
+        updates
+            .iter()
+            .map(|u| {
+                let scheme = schemes
+                    .get(u.source.as_str())
+                    .copied()
+                    .unwrap_or(default_scheme);
+                args.score_weights
+                    .weight(scheme.bump(&u.current_version, &u.latest_version))
+            })
+            .sum::<u64>()
+    });
+
+    // Suggest version bumps in the recipe files themselves
+    let edits = if args.write_suggestions {
+        write_suggestions(&recipes, &updates, args.dry_run)?
+    } else {
+        vec![]
+    };
+
+    // Never save a baseline from a partial run
+    if let Some(path) = args
+        .save_baseline
+        .as_ref()
+        .filter(|_| !interrupted && failures.is_empty())
+    {
+        catalog.save(path)?;
+    }
+
+    match global.format {
+        Format::Json => output::print_update_json(global, &updates, &failures, interrupted)?,
+        // CSV has no room for failures, which are reported on stderr instead
+        Format::Csv => {
+            output::print_update_csv(&updates)?;
+            for failure in &failures {
+                eprintln!(
+                    "{} failed to look up {}: {}",
+                    "Warning:".yellow().bold(),
+                    failure.upstream,
+                    failure.error
+                );
+            }
+        }
+        Format::Prometheus => output::print_update_metrics(&updates, failures.len()),
+        Format::Junit => {
+            // Recipes are errored by the first of their upstreams that failed
+            let errored = recipes
+                .iter()
+                .filter_map(|r| {
+                    let mut upstreams = r.monitoring.iter().flat_map(updates::Upstream::all);
+                    upstreams
+                        .find_map(|upstream| failures.iter().find(|f| f.upstream == upstream))
+                        .map(|f| (r.name.as_str(), f.error.as_str()))
+                })
+                .collect::<HashMap<_, _>>();
+            let checked = recipes
+                .iter()
+                .filter(|r| {
+                    errored.contains_key(r.name.as_str())
+                        || r.monitoring
+                            .iter()
+                            .flat_map(updates::Upstream::all)
+                            .any(|upstream| match upstream {
+                                updates::Upstream::ReleaseMonitoring(id) => {
+                                    latest_versions.contains_key(&id)
+                                }
+                                updates::Upstream::Github(repo) => {
+                                    github_versions.contains_key(&repo)
+                                }
+                            })
+                })
+                .collect::<Vec<_>>();
+            output::print_junit(&checked, &outdated, &errored);
+        }
+        Format::Human => {
+            output::print_updates(global, &updates, &no_stable, &held_back, &downgrades);
+
+            // Print the update debt score
+            if let Some(score) = debt_score {
+                println!("\nUpdate debt score: {}", score.to_string().yellow().bold());
+            }
+
+            // Print the suggested recipe edits
+            if args.write_suggestions {
+                output::print_suggestions(&edits, args.dry_run);
+            }
+
+            // Print upstream sources that have vanished
+            if let Some(dead) = &dead_sources {
+                output::print_dead_sources(dead);
+            }
+
+            // Print changes since the baseline
+            if let Some(diff) = baseline_diff {
+                output::print_baseline_diff(&diff);
+            }
+
+            // Print the upstreams that couldn't be checked
+            if !failures.is_empty() {
+                output::print_failures(&failures);
+            }
+
+            // Print how long was spent waiting on rate limits
+            let rate_limited = client.rate_limited_for();
+            if !rate_limited.is_zero() {
+                println!(
+                    "\nWaited {} on release-monitoring.org rate limits",
+                    format!("{}s", rate_limited.as_secs()).yellow()
+                );
+            }
+
+            output::print_footer(global, recipes.len(), "recipes");
+
+            if interrupted {
+                println!(
+                    "\n{}",
+                    "Interrupted, only part of the projects were checked".yellow()
+                );
+            }
+        }
+    }
+
+    // An interrupted run is an error, while failed lookups are only reported in the
+    // output unless --exit-code asks for them to fail the run with status 2
+    if interrupted {
+        return Err(Error::Interrupted);
+    }
+    if args.exit_code && !failures.is_empty() {
+        return Err(Error::LookupFailed {
+            failed: failures.len(),
+            rate_limited: failures.iter().filter(|f| f.rate_limited).count(),
+        });
+    }
+    Ok(update_count)
+}
+
+// This function rewrites the version of each outdated recipe to its suggested
+// update, or only computes the edits when doing a dry run. Recipe versions with
+// an epoch, a `v` prefix or a packaging release are deliberately left alone, as
+// replacing them with the bare upstream version would drop that decoration.
+fn write_suggestions<'a>(
+    recipes: &'a [Recipe],
+    updates: &[RequiredUpdate],
+    dry_run: bool,
+) -> Result<Vec<(&'a Recipe, recipes::edit::VersionEdit)>, Error> {
+    let mut edits = vec![];
+    for update in updates {
+        // The recipe version tracks its first upstream, so don't suggest the
+        // version of any other upstream it bundles
+        for recipe in recipes.iter().filter(|r| {
+            r.name == update.source
+                && r.version == update.current_version
+                && version::normalize_version(&r.version) == r.version
+                && r.monitoring
+                    .as_ref()
+                    .is_some_and(|m| m.project_id == update.project_id)
+        }) {
+            let contents = fs::read_to_string(&recipe.path)?;
+            let Some(edit) = recipes::edit::suggest_version(
+                &contents,
+                &update.current_version,
+                &update.latest_version,
+            ) else {
+                continue;
+            };
+            if !dry_run {
+                state::write_atomic(&recipe.path, edit.apply(&contents))?;
+            }
+            edits.push((recipe, edit));
+        }
+    }
+    Ok(edits)
+}
 
-    // Filter and sort updates
-    let mut updates: Vec<_> = latest_recipes.into_iter().flatten().flatten().collect();
-    updates.sort_by(|a, b| a.source.cmp(&b.source));
+/// A CVE affecting a package
+#[derive(serde::Serialize)]
+struct SecurityFinding {
+    source: String,
+    cve_id: String,
+    score: Option<f64>,
+    severity: data::nvd::Severity,
+}
+
+impl SecurityFinding {
+    fn new(source: &str, item: &data::nvd::CveItem) -> Self {
+        Self {
+            source: source.to_string(),
+            cve_id: item.id().to_string(),
+            score: item.base_score(),
+            severity: item.effective_severity(),
+        }
+    }
+}
+
+/// Matches the CPEs declared by the recipes against a CVE feed and displays the findings
+fn check_security(
+    root: impl AsRef<Path>,
+    global: &GlobalArgs,
+    args: &SecurityArgs,
+) -> Result<(), Error> {
+    let recipes = scan_recipes(root, global)?;
+
+    let feed = match &args.feed {
+        Some(path) => load_feed(path)?,
+        None => data::nvd::CveData { cve_items: vec![] },
+    };
+
+    // Recipes without any CPE entries have nothing to match
+    let mut findings = vec![];
+    for recipe in &recipes {
+        let Some(m) = recipe.monitoring.as_ref().filter(|m| !m.cpes.is_empty()) else {
+            continue;
+        };
+        for item in &feed.cve_items {
+            // Only report CVEs whose affected range covers the packaged version
+            let affected = m
+                .cpes
+                .iter()
+                .any(|cpe| item.affects(&cpe.vendor, &cpe.product, &recipe.version));
+            if affected && item.effective_severity().meets(args.min_severity) {
+                findings.push(SecurityFinding::new(&recipe.name, item));
+            }
+        }
+    }
+
+    print_findings(global, findings)?;
+
+    Ok(())
+}
+
+// This function loads a CVE feed from a file, or all `*.json` feeds in a directory.
+// Malformed feeds within a directory are skipped with a warning naming the file.
+fn load_feed(path: &Path) -> Result<data::nvd::CveData, Error> {
+    if !path.is_dir() {
+        return Ok(data::nvd::CveData::from_json(&fs::read_to_string(path)?)?);
+    }
+
+    let mut files = fs::read_dir(path)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let mut cve_items = vec![];
+    for file in files {
+        match data::nvd::CveData::from_json(&fs::read_to_string(&file)?) {
+            Ok(feed) => cve_items.extend(feed.cve_items),
+            Err(e) => eprintln!(
+                "{} skipping malformed feed {}: {}",
+                "Warning:".yellow().bold(),
+                file.display(),
+                e
+            ),
+        }
+    }
 
-    // Calculate column widths for pretty printing
-    let max_source_len = updates.iter().map(|u| u.source.len()).max().unwrap_or(0);
-    let max_current_version_len = updates
+    Ok(data::nvd::CveData { cve_items })
+}
+
+/// Queries NVD for the CVEs affecting a single CPE and displays them
+async fn lookup_cpe(
+    client: &data::Client,
+    global: &GlobalArgs,
+    cpe: &data::nvd::Cpe,
+    min_severity: data::nvd::Severity,
+) -> Result<(), Error> {
+    let name = format!(
+        "cpe:2.3:{}:{}:{}:{}:*:*:*:*:*:*:*",
+        cpe.part, cpe.vendor, cpe.product, cpe.version
+    );
+    let items = data::nvd::v2::fetch_cves(client, &name).await?;
+
+    let source = format!("{}:{}:{}", cpe.vendor, cpe.product, cpe.version);
+    let findings = items
         .iter()
-        .map(|u| u.current_version.len())
+        .filter(|item| item.affects(&cpe.vendor, &cpe.product, &cpe.version))
+        .filter(|item| item.effective_severity().meets(min_severity))
+        .map(|item| SecurityFinding::new(&source, item))
+        .collect();
+
+    print_findings(global, findings)?;
+
+    Ok(())
+}
+
+// This function prints security findings, most severe first
+fn print_findings(global: &GlobalArgs, mut findings: Vec<SecurityFinding>) -> Result<(), Error> {
+    findings.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| a.source.cmp(&b.source))
+            .then_with(|| a.cve_id.cmp(&b.cve_id))
+    });
+
+    if global.format == Format::Json {
+        return output::print_json(global, &findings);
+    }
+
+    let max_source_len = findings
+        .iter()
+        .map(|f| f.source.len())
+        .chain(["Package".len()])
         .max()
         .unwrap_or(0);
-    let max_latest_version_len = updates
+    let max_cve_len = findings
         .iter()
-        .map(|u| u.latest_version.len())
+        .map(|f| f.cve_id.len())
+        .chain(["CVE".len()])
         .max()
         .unwrap_or(0);
+    let max_score_len = 5;
 
-    // Print results
-    println!(
-        "\nTotal packages to update: {}\n",
-        updates.len().to_string().yellow()
-    );
-    // Print header
     println!(
-        "{:width_source$} {:width_current$} {:width_latest$}",
-        "Package".bold(),
-        "Current".bold(),
-        "Latest".bold(),
-        width_source = max_source_len,
-        width_current = max_current_version_len,
-        width_latest = max_latest_version_len
+        "\nTotal vulnerabilities found: {}\n",
+        findings.len().to_string().yellow()
     );
 
-    // Print separator line
-    println!(
-        "{:-<width_source$} {:-<width_current$} {:-<width_latest$}",
-        "",
-        "",
-        "",
-        width_source = max_source_len,
-        width_current = max_current_version_len,
-        width_latest = max_latest_version_len
-    );
+    if !global.no_header {
+        // Print header
+        println!(
+            "{:width_source$} {:width_cve$} {:>width_score$} {}",
+            "Package".bold(),
+            "CVE".bold(),
+            "Score".bold(),
+            "Severity".bold(),
+            width_source = max_source_len,
+            width_cve = max_cve_len,
+            width_score = max_score_len
+        );
 
-    // Print updates
-    for update in updates {
+        // Print separator
         println!(
-            "{:<width_source$} {:<width_current$} {:<width_latest$}",
-            update.source.cyan(),
-            update.current_version.red(),
-            update.latest_version.green(),
+            "{:-<width_source$} {:-<width_cve$} {:-<width_score$} {:-<8}",
+            "",
+            "",
+            "",
+            "",
             width_source = max_source_len,
-            width_current = max_current_version_len,
-            width_latest = max_latest_version_len
+            width_cve = max_cve_len,
+            width_score = max_score_len
         );
     }
 
+    for finding in &findings {
+        let severity_color = match finding.severity {
+            data::nvd::Severity::Critical | data::nvd::Severity::High => "red",
+            data::nvd::Severity::Medium => "yellow",
+            data::nvd::Severity::Low => "green",
+            data::nvd::Severity::None | data::nvd::Severity::Unknown => "white",
+        };
+        let score = finding
+            .score
+            .map(|s| format!("{:.1}", s))
+            .unwrap_or_else(|| "-".to_string());
+
+        println!(
+            "{:<width_source$} {:<width_cve$} {:>width_score$} {}",
+            finding.source.cyan(),
+            finding.cve_id,
+            score,
+            finding.severity.to_string().color(severity_color).bold(),
+            width_source = max_source_len,
+            width_cve = max_cve_len,
+            width_score = max_score_len
+        );
+    }
+
+    output::print_footer(global, findings.len(), "vulnerabilities");
     Ok(())
 }
 
-/// Fetches and displays the current builds from Summit
-async fn list_builds() -> Result<(), Box<dyn std::error::Error>> {
-    let client = reqwest::Client::new();
-
-    // Fetch 3 pages of results
-    let mut all_items = Vec::new();
-    for page in 0..=3 {
-        let response = client
-            .get(format!(
-                "https://dash.serpentos.com/api/v1/tasks/enumerate?pageNumber={}",
-                page
-            ))
-            .send()
-            .await?
-            .json::<data::summit::TaskEnumerateResponse>()
-            .await?;
-        all_items.extend(response.items);
-    }
-
-    // Calculate column widths
-    let max_id_len = 8; // Fixed width for ID
-    let max_pkg_len = 50; // Fixed max width for build ID
-    let max_arch_len = all_items
-        .iter()
-        .map(|t| t.architecture.len())
-        .max()
-        .unwrap_or(10);
-    let max_status_len = 10; // Fixed width for status
+// This function loads the version cache, discarding it with a warning when corrupt
+fn load_version_cache(path: &Path) -> data::cache::VersionCache {
+    data::cache::VersionCache::load(path).unwrap_or_else(|e| {
+        eprintln!(
+            "{} discarding unreadable cache {}: {}",
+            "Warning:".yellow().bold(),
+            path.display(),
+            e
+        );
+        data::cache::VersionCache::default()
+    })
+}
+
+/// Fetches the upstream versions of all monitored recipes into the version cache
+async fn refresh(
+    client: &data::Client,
+    root: impl AsRef<Path>,
+    global: &GlobalArgs,
+    args: &RefreshArgs,
+) -> Result<(), Error> {
+    let Some(path) = data::cache::VersionCache::default_path() else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "unable to locate the cache directory, set XDG_CACHE_HOME or HOME",
+        )
+        .into());
+    };
+    let mut cache = load_version_cache(&path);
+
+    let recipes = scan_recipes(root, global)?;
+
+    // Coalesce recipes by project id so each upstream project is only fetched once
+    let projects = updates::projects(&recipes);
+
+    let pb = progress_bar(global, projects.len());
+    let client = &client.clone().on_rate_limit({
+        let pb = pb.clone();
+        move |wait| pb.set_message(format!("(rate limited, waiting {}s)", wait.as_secs()))
+    });
+
+    let mut ids = projects.keys().copied().collect::<Vec<_>>();
+    ids.sort();
+
+    let mut refreshed = 0;
+    let mut failed = 0;
+    let mut lookups = std::pin::pin!(data::updates::stream_latest_versions(
+        client,
+        data::updates::RELEASE_MONITORING_URL,
+        &ids,
+        args.jobs,
+    ));
+    while let Some((chunk, result)) = lookups.next().await {
+        pb.inc(chunk.len() as u64);
+        let mut found = match result {
+            Ok(found) => found,
+            Err(data::Error::NetworkDisabled) => {
+                pb.finish_and_clear();
+                return Err(data::Error::NetworkDisabled.into());
+            }
+            Err(e) => {
+                tracing::info!(projects = ?chunk, error = %e, "failed to fetch versions");
+                failed += chunk.len();
+                continue;
+            }
+        };
+
+        for &project_id in chunk {
+            let Some(versions) = found.remove(&project_id) else {
+                tracing::info!(project_id, "project not found");
+                failed += 1;
+                continue;
+            };
+            refreshed += 1;
+            cache.entries.insert(
+                project_id,
+                data::cache::Entry {
+                    fetched_at: data::cache::now(),
+                    paths: projects[&project_id]
+                        .iter()
+                        .map(|r| r.path.clone())
+                        .collect(),
+                    versions,
+                },
+            );
+        }
+    }
+    pb.finish_and_clear();
+
+    let pruned = if args.prune {
+        cache.prune(|p| p.exists())
+    } else {
+        0
+    };
+
+    cache.save(&path)?;
 
-    // Print header
     println!(
-        "\n{:>id_width$} {:pkg_width$} {:arch_width$} {:status_width$}",
-        "ID".bold(),
-        "Package".bold(),
-        "Arch".bold(),
-        "Status".bold(),
-        id_width = max_id_len,
-        pkg_width = max_pkg_len,
-        arch_width = max_arch_len,
-        status_width = max_status_len
+        "Refreshed {} projects into {}",
+        refreshed.to_string().green(),
+        path.display()
     );
+    if failed > 0 {
+        println!("Failed to fetch {} projects", failed.to_string().red());
+    }
+    if args.prune {
+        println!("Pruned {} stale entries", pruned.to_string().yellow());
+    }
+
+    Ok(())
+}
+
+/// Lists the recipes by declared license and flags those that need attention
+fn audit_licenses(root: impl AsRef<Path>, global: &GlobalArgs) -> Result<(), Error> {
+    let mut recipes = scan_recipes(root, global)?;
+    recipes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut by_license = std::collections::BTreeMap::<&str, Vec<&str>>::new();
+    let mut missing = vec![];
+    let mut invalid = vec![];
+    for recipe in &recipes {
+        if recipe.licenses.iter().all(|l| l.trim().is_empty()) {
+            missing.push(recipe);
+            continue;
+        }
+        for license in recipe.licenses.iter().map(|l| l.trim()) {
+            if license.is_empty() {
+                continue;
+            }
+            by_license.entry(license).or_default().push(&recipe.name);
+            if !recipes::license::is_spdx_expression(license) {
+                invalid.push((recipe, license));
+            }
+        }
+    }
+
+    if global.format == Format::Json {
+        return output::print_json(
+            global,
+            serde_json::json!({
+                "licenses": by_license,
+                "missing": missing
+                    .iter()
+                    .map(|r| serde_json::json!({ "name": r.name, "path": r.path }))
+                    .collect::<Vec<_>>(),
+                "invalid": invalid
+                    .iter()
+                    .map(|(r, license)| serde_json::json!({ "name": r.name, "license": license }))
+                    .collect::<Vec<_>>(),
+            }),
+        );
+    }
 
-    // Print separator
     println!(
-        "{:-<id_width$} {:-<pkg_width$} {:-<arch_width$} {:-<status_width$}",
-        "",
-        "",
-        "",
-        "",
-        id_width = max_id_len,
-        pkg_width = max_pkg_len,
-        arch_width = max_arch_len,
-        status_width = max_status_len
+        "\nTotal licenses in use: {}\n",
+        by_license.len().to_string().yellow()
     );
+    for (license, packages) in &by_license {
+        println!(
+            "{} ({})",
+            license.bold(),
+            packages.len().to_string().yellow()
+        );
+        println!("  {}", packages.join(", ").cyan());
+    }
 
-    // First print building items
-    for task in all_items
-        .iter()
-        .filter(|t| matches!(t.status, data::summit::BuildStatus::Building))
-    {
-        print_task(task, max_id_len, max_pkg_len, max_arch_len);
+    if !missing.is_empty() {
+        println!("\nMissing license: {}\n", missing.len().to_string().red());
+        for recipe in &missing {
+            println!("{} {}", recipe.name.cyan(), recipe.path.display());
+        }
     }
 
-    // Then print new items
-    for task in all_items
-        .iter()
-        .filter(|t| matches!(t.status, data::summit::BuildStatus::New))
-    {
-        print_task(task, max_id_len, max_pkg_len, max_arch_len);
+    if !invalid.is_empty() {
+        println!("\nNon-SPDX license: {}\n", invalid.len().to_string().red());
+        for (recipe, license) in &invalid {
+            println!("{} {}", recipe.name.cyan(), license.red());
+        }
     }
 
-    // Finally print remaining items
-    for task in all_items.iter().filter(|t| {
-        !matches!(
-            t.status,
-            data::summit::BuildStatus::Building | data::summit::BuildStatus::New
+    output::print_footer(global, recipes.len(), "recipes");
+
+    Ok(())
+}
+
+/// Fetches and displays the current builds from Summit
+async fn list_builds(
+    client: &data::Client,
+    global: &GlobalArgs,
+    args: &BuildsArgs,
+) -> Result<(), Error> {
+    let all_items = fetch_builds(client, global, args).await?;
+    print_builds(global, args, all_items)
+}
+
+// This function redraws the builds table every interval until interrupted. A
+// failed refresh leaves the previous table up with a warning, and is retried on
+// the next tick.
+async fn watch_builds(
+    client: &data::Client,
+    global: &GlobalArgs,
+    args: &BuildsArgs,
+) -> Result<(), Error> {
+    let interval = std::time::Duration::from_secs(args.interval.max(1));
+    let mut interrupt = std::pin::pin!(tokio::signal::ctrl_c());
+
+    // Redraw in place only for a human on a terminal. Otherwise each refresh is
+    // simply appended, so piped and JSON output stay free of escape sequences.
+    let redraw = global.format == Format::Human && std::io::stdout().is_terminal();
+
+    // Hide the cursor while redrawing, restoring it however the loop ends
+    if redraw {
+        print!("\x1b[?25l");
+    }
+    let result = loop {
+        let fetched = tokio::select! {
+            fetched = fetch_builds(client, global, args) => fetched,
+            _ = &mut interrupt => break Ok(()),
+        };
+        match fetched {
+            Ok(all_items) => {
+                // Clear the screen only once the tasks are in, to avoid flickering
+                if redraw {
+                    print!("\x1b[2J\x1b[H");
+                }
+                if let Err(e) = print_builds(global, args, all_items) {
+                    break Err(e);
+                }
+                if redraw {
+                    println!(
+                        "\n{}",
+                        format!(
+                            "Refreshing every {}s, press Ctrl-C to stop",
+                            interval.as_secs()
+                        )
+                        .dimmed()
+                    );
+                }
+            }
+            Err(e) => eprintln!(
+                "{} {}, retrying in {}s",
+                "Warning:".yellow().bold(),
+                e,
+                interval.as_secs()
+            ),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = &mut interrupt => break Ok(()),
+        }
+    };
+    if redraw {
+        println!("\x1b[?25h");
+    }
+
+    result
+}
+
+// This function fetches every task known to Summit, or only the task selected
+// with --task
+async fn fetch_builds(
+    client: &data::Client,
+    global: &GlobalArgs,
+    args: &BuildsArgs,
+) -> Result<Vec<data::summit::Task>, Error> {
+    if let Some(id) = args.task {
+        let task = data::summit::get_task(
+            client,
+            &global.summit_base_url,
+            global.summit_api_version,
+            id,
         )
-    }) {
-        print_task(task, max_id_len, max_pkg_len, max_arch_len);
+        .await?;
+        return Ok(vec![task]);
+    }
+
+    Ok(data::summit::enumerate_all_tasks(
+        client,
+        &global.summit_base_url,
+        global.summit_api_version,
+    )
+    .await?)
+}
+
+// This function filters, orders and prints the tasks in the selected format
+fn print_builds(
+    global: &GlobalArgs,
+    args: &BuildsArgs,
+    mut all_items: Vec<data::summit::Task>,
+) -> Result<(), Error> {
+    // Summit has no server side project filter, so narrow the tasks down here
+    if let Some(project) = args.project {
+        all_items.retain(|t| t.project_id == project);
+        if all_items.is_empty() && global.format == Format::Human {
+            println!(
+                "No builds found for project {}",
+                project.to_string().yellow()
+            );
+            return Ok(());
+        }
+    }
+
+    if !args.status.is_empty() {
+        all_items.retain(|t| args.status.contains(&t.status));
+    }
+    if let Some(arch) = &args.arch {
+        all_items.retain(|t| t.architecture == *arch);
+    }
+
+    // Order the tasks so that consecutive runs are stable and diffable, using the
+    // task id to break ties
+    match args.sort {
+        BuildSort::Updated => all_items.sort_by(|a, b| {
+            b.ts_updated
+                .cmp(&a.ts_updated)
+                .then_with(|| b.id.cmp(&a.id))
+        }),
+        BuildSort::Id => all_items.sort_by(|a, b| b.id.cmp(&a.id)),
+    }
+
+    // The Prometheus metrics are already an aggregate
+    if args.summary && global.format != Format::Prometheus {
+        return output::print_build_summary(global, &all_items);
+    }
+
+    match global.format {
+        Format::Json => output::print_json(global, &all_items)?,
+        Format::Prometheus => output::print_build_metrics(&all_items),
+        _ => output::print_build_table(global, args.with_log, &all_items),
     }
 
     Ok(())
 }
 
-fn print_task(
-    task: &data::summit::Task,
-    max_id_len: usize,
-    max_pkg_len: usize,
-    max_arch_len: usize,
-) {
-    let status_color = match task.status {
-        data::summit::BuildStatus::New => "cyan",
-        data::summit::BuildStatus::Failed => "red",
-        data::summit::BuildStatus::Building => "yellow",
-        data::summit::BuildStatus::Publishing => "blue",
-        data::summit::BuildStatus::Completed => "green",
-        data::summit::BuildStatus::Blocked => "red",
+#[tokio::main]
+async fn main() -> ExitCode {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    init_logging(cli.global.verbose);
+
+    // Defaults may come from the nearest ent.toml, but flags always win
+    let config = match Config::load(std::env::current_dir().unwrap_or_default()) {
+        Ok(config) => config,
+        Err(e) => {
+            report_error(&e.into(), &cli.global);
+            return ExitCode::FAILURE;
+        }
     };
+    if let Err(e) = cli.apply_config(&matches, &config) {
+        let path = config
+            .path
+            .as_deref()
+            .unwrap_or(Path::new(config::FILE_NAME));
+        Cli::command()
+            .error(
+                ErrorKind::InvalidValue,
+                format!("invalid value in {}: {e}", path.display()),
+            )
+            .exit();
+    }
 
-    let truncated_build_id = {
-        let id = task.build_id.split('/').last().unwrap_or(&task.build_id);
-        if id.len() > 50 {
-            format!("{}...", &id[..47])
-        } else {
-            id.to_string()
+    match run(&cli, &config).await {
+        Ok(code) => code,
+        Err(e) => {
+            report_error(&e, &cli.global);
+            if cli.exit_code() {
+                ExitCode::from(2)
+            } else {
+                ExitCode::FAILURE
+            }
         }
+    }
+}
+
+// This function sends the logs of ent itself to stderr, keeping stdout free for
+// the output. Without -v only warnings are logged, which are meant for the user
+// and so go without the timestamps and targets that help when debugging.
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => tracing::Level::WARN,
+        1 => tracing::Level::INFO,
+        2 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
     };
 
-    println!(
-        "{:>id_width$} {:<pkg_width$} {:<arch_width$} {}",
-        task.id.to_string().bold(),
-        truncated_build_id.cyan(),
-        task.architecture,
-        format!("{:?}", task.status).color(status_color).bold(),
-        id_width = max_id_len,
-        pkg_width = max_pkg_len,
-        arch_width = max_arch_len,
-    );
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+    let layer = if verbose == 0 {
+        layer.without_time().with_target(false).boxed()
+    } else {
+        layer.boxed()
+    };
+
+    tracing_subscriber::registry()
+        .with(layer)
+        .with(tracing_subscriber::filter::Targets::new().with_target("ent", level))
+        .init();
 }
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+
+// This function dispatches the parsed command line, returning the exit status
+async fn run(cli: &Cli, config: &Config) -> Result<ExitCode, Error> {
+    // Zero leaves the choice to rayon, which uses one thread per CPU
+    let _ = rayon::ThreadPoolBuilder::new()
+        .num_threads(cli.global.threads.unwrap_or(0))
+        .build_global();
+    let github_token = std::env::var("GITHUB_TOKEN")
+        .ok()
+        .or_else(|| config.github_token.clone());
+    let client = data::Client::new(cli.offline()).with_github_token(github_token);
 
     match &cli.command {
-        Commands::Refresh => {
-            todo!("Implement refresh");
+        Commands::Refresh(args) => {
+            refresh(&client, &cli.global.root, &cli.global, args).await?;
         }
         Commands::Check { check_command } => match check_command {
-            CheckCommands::Updates => {
-                println!("Checking for updates...");
-                check_updates(".").await?;
-            }
-            CheckCommands::Security => {
-                todo!("Implement security check");
+            CheckCommands::Updates(args) => {
+                if cli.global.format == Format::Human {
+                    println!("Checking for updates...");
+                }
+                let updates = check_updates(&client, &cli.global.root, &cli.global, args).await?;
+                if args.exit_code && updates > 0 {
+                    return Ok(ExitCode::from(1));
+                }
             }
+            // Matching against a local feed needs no network, and with --offline
+            // the client refuses any request with `NetworkDisabled`
+            CheckCommands::Security(args) => match &args.cpe {
+                Some(cpe) => lookup_cpe(&client, &cli.global, cpe, args.min_severity).await?,
+                None => check_security(&cli.global.root, &cli.global, args)?,
+            },
         },
-        Commands::Builds => {
-            list_builds().await?;
+        Commands::Builds(args) if args.watch => {
+            watch_builds(&client, &cli.global, args).await?;
+        }
+        Commands::Builds(args) => {
+            list_builds(&client, &cli.global, args).await?;
         }
+        Commands::Parse(args) => {
+            let recipe = parse_recipe(&args.path, args.recipe_type, &cli.global)?;
+            print_recipe(&cli.global, &recipe)?;
+        }
+        Commands::Audit { audit_command } => match audit_command {
+            AuditCommands::Licenses => audit_licenses(&cli.global.root, &cli.global)?,
+        },
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // This function parses the arguments and fills in the defaults from the config
+    fn cli(args: &[&str], config: &Config) -> Cli {
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap();
+        cli.apply_config(&matches, config).unwrap();
+        cli
+    }
+
+    // This function picks the options of `check updates`
+    fn updates_args(cli: &Cli) -> &UpdatesArgs {
+        match &cli.command {
+            Commands::Check {
+                check_command: CheckCommands::Updates(args),
+            } => args,
+            _ => panic!("not `check updates`"),
+        }
+    }
+
+    #[test]
+    fn config_overrides_built_in_defaults() {
+        let root = tempfile::tempdir().unwrap();
+        let config = Config {
+            root: Some(root.path().to_path_buf()),
+            jobs: Some(4),
+            cache_ttl: Some("1d".to_string()),
+            ..Default::default()
+        };
+
+        let cli = cli(&["ent", "check", "updates"], &config);
+
+        assert_eq!(cli.global.root, root.path());
+        assert_eq!(updates_args(&cli).jobs, 4);
+        assert_eq!(
+            updates_args(&cli).cache_ttl,
+            std::time::Duration::from_secs(24 * 60 * 60)
+        );
+    }
+
+    #[test]
+    fn flags_override_config() {
+        let root = tempfile::tempdir().unwrap();
+        let config = Config {
+            root: Some(root.path().join("missing")),
+            jobs: Some(4),
+            ..Default::default()
+        };
+
+        let cli = cli(
+            &["ent", "--root", ".", "check", "updates", "--jobs", "2"],
+            &config,
+        );
+
+        assert_eq!(cli.global.root, Path::new("."));
+        assert_eq!(updates_args(&cli).jobs, 2);
+    }
+
+    #[test]
+    fn empty_config_keeps_built_in_defaults() {
+        let cli = cli(&["ent", "check", "updates"], &Config::default());
+
+        assert_eq!(cli.global.root, Path::new("."));
+        assert_eq!(updates_args(&cli).jobs, 32);
+    }
+
+    #[test]
+    fn security_offline_disables_the_network() {
+        let config = Config::default();
+        let offline = cli(
+            &["ent", "check", "security", "--offline", "--feed", "."],
+            &config,
+        );
+        let online = cli(&["ent", "check", "security", "--feed", "."], &config);
+        let no_network = cli(&["ent", "--no-network", "check", "updates"], &config);
+
+        assert!(offline.offline());
+        assert!(!online.offline());
+        assert!(no_network.offline());
+    }
+
+    // This function writes a ypkg recipe monitoring project 1, returning it parsed
+    fn recipe(root: &Path, name: &str, version: &str) -> Recipe {
+        let dir = root.join(name);
+        fs::create_dir(&dir).unwrap();
+        fs::write(
+            dir.join("package.yml"),
+            format!("name: {name}\nversion: \"{version}\"\n"),
+        )
+        .unwrap();
+        fs::write(dir.join("monitoring.yaml"), "releases:\n  id: 1\n").unwrap();
+
+        let parser = (recipes::scan::find_parser("ypkg").unwrap().parser)();
+        parser
+            .parse(&dir.join("package.yml"), &Default::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn suggestions_leave_decorated_versions_alone() {
+        let root = tempfile::tempdir().unwrap();
+        let recipes = [
+            recipe(root.path(), "nano", "7.2"),
+            recipe(root.path(), "epoch", "1:7.2"),
+            recipe(root.path(), "prefixed", "v7.2"),
+        ];
+        let updates = recipes
+            .iter()
+            .map(|r| RequiredUpdate {
+                source: r.name.clone(),
+                current_version: r.version.clone(),
+                latest_version: "8.0".to_string(),
+                project_id: 1,
+                github: None,
+            })
+            .collect::<Vec<_>>();
+
+        let edits = write_suggestions(&recipes, &updates, false).unwrap();
+
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].0.name, "nano");
+        let contents =
+            |name: &str| fs::read_to_string(root.path().join(name).join("package.yml")).unwrap();
+        assert_eq!(contents("nano"), "name: nano\nversion: \"8.0\"\n");
+        assert_eq!(contents("epoch"), "name: epoch\nversion: \"1:7.2\"\n");
+        assert_eq!(contents("prefixed"), "name: prefixed\nversion: \"v7.2\"\n");
+    }
 }
@@ -0,0 +1,829 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Rendering of command results in the supported output formats
+
+use std::collections::{BTreeMap, HashMap};
+
+use colored::Colorize;
+use ent::{
+    data,
+    recipes::{self, Recipe},
+    updates::{self, RequiredUpdate},
+};
+
+use crate::{BaselineDiff, DeadSource, Error, Format, GlobalArgs};
+
+// This function formats a unix timestamp as an RFC 3339 UTC date time
+fn format_timestamp(secs: i64) -> String {
+    let days = secs.div_euclid(86400);
+    let time = secs.rem_euclid(86400);
+
+    // Convert days since the epoch to a civil date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+// This function prints a provenance footer below human readable tables
+pub fn print_footer(global: &GlobalArgs, count: usize, noun: &str) {
+    if global.no_header || global.format != Format::Human {
+        return;
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    println!(
+        "\n{}",
+        format!(
+            "Generated by ent {} at {} against {} {}",
+            env!("CARGO_PKG_VERSION"),
+            format_timestamp(now),
+            count,
+            noun
+        )
+        .dimmed()
+    );
+}
+
+// This function prints a legend of the table colors when requested with --legend
+fn print_legend(global: &GlobalArgs, legend: &[(&str, &str)]) {
+    if !global.legend || global.format != Format::Human {
+        return;
+    }
+
+    println!("\n{}", "Legend:".bold());
+    for (color, meaning) in legend {
+        println!("  {} {}", "■".color(*color), meaning);
+    }
+}
+
+// This function applies the requested key convention to a JSON document
+pub fn json_output(global: &GlobalArgs, value: serde_json::Value) -> serde_json::Value {
+    match global.json_case {
+        Some(case) => case.apply(value),
+        None => value,
+    }
+}
+
+// This function prints a value as JSON to stdout, applying the requested key convention
+pub fn print_json(global: &GlobalArgs, value: impl serde::Serialize) -> Result<(), Error> {
+    let value = json_output(global, serde_json::to_value(value)?);
+    println!("{}", serde_json::to_string_pretty(&value)?);
+    Ok(())
+}
+
+/// Colors used by the `check updates` tables
+const UPDATES_LEGEND: &[(&str, &str)] = &[
+    ("cyan", "package name"),
+    ("red", "current version, behind upstream"),
+    ("green", "latest upstream version to update to"),
+    (
+        "yellow",
+        "upstream version that needs a closer look: prerelease only, beyond the update policy, or older than the recipe",
+    ),
+];
+
+// This function prints the human readable tables of updates, followed by the
+// recipes needing a closer look
+pub fn print_updates(
+    global: &GlobalArgs,
+    updates: &[RequiredUpdate],
+    no_stable: &[RequiredUpdate],
+    held_back: &[RequiredUpdate],
+    downgrades: &[RequiredUpdate],
+) {
+    let verbose = global.verbose > 0;
+
+    // Shorten long package names if requested
+    let name_limit = global.truncate_names.filter(|_| !global.no_truncate);
+    let name = |update: &RequiredUpdate| match name_limit {
+        Some(limit) => truncate(&update.source, limit),
+        None => update.source.clone(),
+    };
+
+    // Calculate column widths for pretty printing
+    let max_source_len = updates
+        .iter()
+        .chain(no_stable)
+        .chain(held_back)
+        .chain(downgrades)
+        .map(|u| name(u).len())
+        .max()
+        .unwrap_or(0);
+    let max_current_version_len = updates
+        .iter()
+        .chain(no_stable)
+        .chain(held_back)
+        .chain(downgrades)
+        .map(|u| u.current_version.len())
+        .max()
+        .unwrap_or(0);
+    let max_latest_version_len = updates
+        .iter()
+        .chain(no_stable)
+        .chain(held_back)
+        .chain(downgrades)
+        .map(|u| u.latest_version.len())
+        .max()
+        .unwrap_or(0);
+
+    // Print results
+    print_legend(global, UPDATES_LEGEND);
+    print!(
+        "\nTotal packages to update: {}",
+        updates.len().to_string().yellow()
+    );
+    if !downgrades.is_empty() {
+        print!(
+            ", possible wrong monitoring id: {}",
+            downgrades.len().to_string().yellow()
+        );
+    }
+    println!("\n");
+    // In verbose mode, link each update to its release-monitoring project or
+    // GitHub releases
+    let project_url = |update: &RequiredUpdate| match &update.github {
+        _ if !verbose => String::new(),
+        Some(repo) => format!(" {}", data::updates::github_url(repo)),
+        None => format!(" {}", data::updates::project_url(update.project_id)),
+    };
+    let max_url_len = updates
+        .iter()
+        .map(|u| project_url(u).len())
+        .max()
+        .unwrap_or(0);
+
+    if !global.no_header {
+        // Print header
+        println!(
+            "{:width_source$} {:width_current$} {:width_latest$}{}",
+            "Package".bold(),
+            "Current".bold(),
+            "Latest".bold(),
+            if verbose { " Project" } else { "" }.bold(),
+            width_source = max_source_len,
+            width_current = max_current_version_len,
+            width_latest = max_latest_version_len
+        );
+
+        // Print separator line
+        println!(
+            "{:-<width_source$} {:-<width_current$} {:-<width_latest$}{:-<width_url$}",
+            "",
+            "",
+            "",
+            if verbose { " " } else { "" },
+            width_source = max_source_len,
+            width_current = max_current_version_len,
+            width_latest = max_latest_version_len,
+            width_url = max_url_len
+        );
+    }
+
+    // Print updates
+    for update in updates {
+        println!(
+            "{:<width_source$} {:<width_current$} {:<width_latest$}{}",
+            name(update).cyan(),
+            update.current_version.red(),
+            update.latest_version.green(),
+            project_url(update).dimmed(),
+            width_source = max_source_len,
+            width_current = max_current_version_len,
+            width_latest = max_latest_version_len
+        );
+    }
+
+    // Upstream versions older than the recipe's are most likely the wrong project
+    for update in downgrades {
+        println!(
+            "{:<width_source$} {:<width_current$} {:<width_latest$} {}",
+            name(update).cyan(),
+            update.current_version,
+            update.latest_version.yellow(),
+            "! possible wrong monitoring id".yellow().bold(),
+            width_source = max_source_len,
+            width_current = max_current_version_len,
+            width_latest = max_latest_version_len
+        );
+    }
+
+    // Print packages that only have prereleases upstream
+    if !no_stable.is_empty() {
+        println!(
+            "\nNo stable upstream release: {}\n",
+            no_stable.len().to_string().yellow()
+        );
+        for update in no_stable {
+            println!(
+                "{:<width_source$} {:<width_current$} {:<width_latest$}",
+                name(update).cyan(),
+                update.current_version,
+                update.latest_version.yellow(),
+                width_source = max_source_len,
+                width_current = max_current_version_len,
+                width_latest = max_latest_version_len
+            );
+        }
+    }
+
+    // Print packages with upstream releases beyond their update policy
+    if !held_back.is_empty() {
+        println!(
+            "\nHeld back by update policy: {}\n",
+            held_back.len().to_string().yellow()
+        );
+        for update in held_back {
+            println!(
+                "{:<width_source$} {:<width_current$} {:<width_latest$}",
+                name(update).cyan(),
+                update.current_version,
+                update.latest_version.yellow(),
+                width_source = max_source_len,
+                width_current = max_current_version_len,
+                width_latest = max_latest_version_len
+            );
+        }
+    }
+}
+
+// This function prints the updates as JSON, wrapped in an envelope whose `partial`
+// flag is set when the run was interrupted or upstreams failed to be looked up
+pub fn print_update_json(
+    global: &GlobalArgs,
+    updates: &[RequiredUpdate],
+    failures: &[updates::Failure],
+    interrupted: bool,
+) -> Result<(), Error> {
+    print_json(global, update_json(updates, failures, interrupted))
+}
+
+// This function builds the JSON envelope of the updates, which keeps the same shape
+// whether or not the results are partial
+fn update_json(
+    updates: &[RequiredUpdate],
+    failures: &[updates::Failure],
+    interrupted: bool,
+) -> serde_json::Value {
+    serde_json::json!({
+        "partial": interrupted || !failures.is_empty(),
+        "updates": updates,
+        "errors": failures.len(),
+        "failures": failures,
+    })
+}
+
+// This function escapes a Prometheus label value
+fn prometheus_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// This function prints the updates as CSV, quoting fields as needed
+pub fn print_update_csv(updates: &[RequiredUpdate]) -> Result<(), Error> {
+    write_update_csv(std::io::stdout(), updates)
+}
+
+// This function writes the updates as CSV to the given writer
+fn write_update_csv(out: impl std::io::Write, updates: &[RequiredUpdate]) -> Result<(), Error> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(["source", "current_version", "latest_version"])?;
+    for update in updates {
+        writer.write_record([
+            &update.source,
+            &update.current_version,
+            &update.latest_version,
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// This function prints the update metrics in the Prometheus text format
+pub fn print_update_metrics(updates: &[RequiredUpdate], errors: usize) {
+    println!("# HELP ent_outdated_packages Number of packages with an upstream update available.");
+    println!("# TYPE ent_outdated_packages gauge");
+    println!("ent_outdated_packages {}", updates.len());
+
+    println!("# HELP ent_check_errors Number of upstream projects that failed to be checked.");
+    println!("# TYPE ent_check_errors gauge");
+    println!("ent_check_errors {}", errors);
+
+    println!("# HELP ent_package_outdated Whether the package has an upstream update available.");
+    println!("# TYPE ent_package_outdated gauge");
+    for update in updates {
+        println!(
+            "ent_package_outdated{{name=\"{}\"}} 1",
+            prometheus_escape(&update.source)
+        );
+    }
+}
+
+// This function escapes text for use within XML attributes
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// This function prints a JUnit XML report with a testcase per checked recipe,
+// failing those with an update available and erroring those whose upstream
+// failed to be looked up
+pub fn print_junit(
+    checked: &[&Recipe],
+    outdated: &HashMap<&str, &str>,
+    errored: &HashMap<&str, &str>,
+) {
+    let errors = checked
+        .iter()
+        .filter(|r| errored.contains_key(r.name.as_str()))
+        .count();
+    let failures = checked
+        .iter()
+        .filter(|r| !errored.contains_key(r.name.as_str()))
+        .filter(|r| outdated.contains_key(r.name.as_str()))
+        .count();
+
+    println!(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    println!(
+        r#"<testsuite name="ent check updates" tests="{}" failures="{}" errors="{}">"#,
+        checked.len(),
+        failures,
+        errors
+    );
+    for recipe in checked {
+        let name = xml_escape(&recipe.name);
+        let classname = xml_escape(&recipe.path.display().to_string());
+        if let Some(error) = errored.get(recipe.name.as_str()) {
+            println!(r#"  <testcase name="{name}" classname="{classname}">"#);
+            println!(
+                r#"    <error message="{}"/>"#,
+                xml_escape(&format!("lookup failed: {error}"))
+            );
+            println!("  </testcase>");
+            continue;
+        }
+        match outdated.get(recipe.name.as_str()) {
+            Some(latest) => {
+                println!(r#"  <testcase name="{name}" classname="{classname}">"#);
+                println!(
+                    r#"    <failure message="{}"/>"#,
+                    xml_escape(&format!(
+                        "update available: {} -> {}",
+                        recipe.version, latest
+                    ))
+                );
+                println!("  </testcase>");
+            }
+            None => println!(r#"  <testcase name="{name}" classname="{classname}"/>"#),
+        }
+    }
+    println!("</testsuite>");
+}
+
+// This function prints the recipe edits made or suggested by --write-suggestions
+pub fn print_suggestions(edits: &[(&Recipe, recipes::edit::VersionEdit)], dry_run: bool) {
+    println!(
+        "\n{}: {}\n",
+        if dry_run {
+            "Suggested recipe edits"
+        } else {
+            "Recipe edits written"
+        },
+        edits.len().to_string().yellow()
+    );
+    for (recipe, edit) in edits {
+        println!("{}:{}", recipe.path.display().to_string().cyan(), edit.line);
+        println!("{}", format!("-{}", edit.before).red());
+        println!("{}", format!("+{}", edit.after).green());
+    }
+}
+
+// This function prints the dead upstream sources found by `--check-sources`
+pub fn print_dead_sources(dead: &[DeadSource]) {
+    println!(
+        "\nDead upstream sources: {}\n",
+        dead.len().to_string().red()
+    );
+    for d in dead {
+        let status = match &d.status {
+            data::sources::SourceStatus::Dead(code) => code.to_string(),
+            data::sources::SourceStatus::Unreachable(_) => "unreachable".to_string(),
+            data::sources::SourceStatus::Alive => continue,
+        };
+        println!("{} {} {}", d.source.cyan(), d.url, status.red());
+    }
+}
+
+// This function prints the upstreams that failed to be looked up, and why
+pub fn print_failures(failures: &[updates::Failure]) {
+    println!(
+        "\nFailed to look up: {}\n",
+        failures.len().to_string().red()
+    );
+    for failure in failures {
+        let upstream = match &failure.upstream {
+            updates::Upstream::ReleaseMonitoring(id) => data::updates::project_url(*id),
+            updates::Upstream::Github(repo) => data::updates::github_url(repo),
+        };
+        println!("{} {}", upstream.cyan(), failure.error.red());
+    }
+}
+
+// This function prints the changes since a baseline catalog
+pub fn print_baseline_diff(diff: &BaselineDiff) {
+    let sections = [
+        ("Newly outdated", &diff.newly_outdated, "red"),
+        ("Updated", &diff.updated, "green"),
+        ("Added", &diff.added, "cyan"),
+    ];
+
+    println!("\nChanges since baseline:");
+    for (title, sources, color) in sections {
+        println!(
+            "\n{}: {}",
+            title.bold(),
+            sources.len().to_string().color(color)
+        );
+        for source in sources {
+            println!("  {}", source.color(color));
+        }
+    }
+}
+
+/// Colors used by the `builds` table
+const BUILDS_LEGEND: &[(&str, &str)] = &[
+    ("cyan", "new"),
+    ("yellow", "building"),
+    ("blue", "publishing"),
+    ("green", "completed"),
+    ("red", "failed or blocked"),
+];
+
+/// Every build status, with the label it is reported as
+const BUILD_STATUSES: &[(&str, data::summit::BuildStatus)] = &[
+    ("new", data::summit::BuildStatus::New),
+    ("failed", data::summit::BuildStatus::Failed),
+    ("building", data::summit::BuildStatus::Building),
+    ("publishing", data::summit::BuildStatus::Publishing),
+    ("completed", data::summit::BuildStatus::Completed),
+    ("blocked", data::summit::BuildStatus::Blocked),
+];
+
+// This function prints the number of builds in each status in the Prometheus text format
+pub fn print_build_metrics(tasks: &[data::summit::Task]) {
+    println!("# HELP ent_builds Number of recent Summit builds by status.");
+    println!("# TYPE ent_builds gauge");
+    for &(label, status) in BUILD_STATUSES {
+        let count = tasks.iter().filter(|t| t.status == status).count();
+        println!("ent_builds{{status=\"{}\"}} {}", label, count);
+    }
+}
+
+// This function prints the tasks as a table, those building first, then the new
+// ones and finally all others
+pub fn print_build_table(global: &GlobalArgs, with_log: bool, tasks: &[data::summit::Task]) {
+    // Calculate column widths
+    let max_id_len = 8; // Fixed width for ID
+    let log_base = with_log.then_some(global.summit_base_url.as_str());
+    let name_limit = match global.truncate_names {
+        _ if global.no_truncate => None,
+        Some(limit) => Some(limit),
+        None => Some(50),
+    };
+    let max_pkg_len = match name_limit {
+        Some(limit) => limit, // Fixed max width for build ID
+        None => tasks.iter().map(|t| task_name(t).len()).max().unwrap_or(10),
+    };
+    let max_arch_len = tasks
+        .iter()
+        .map(|t| t.architecture.len())
+        .max()
+        .unwrap_or(10);
+    let max_time_len = 8; // Fixed width for the age and run time
+    let max_status_len = 10; // Fixed width for status
+    let now = data::cache::now();
+
+    print_legend(global, BUILDS_LEGEND);
+
+    if !global.no_header {
+        // Print header
+        println!(
+            "\n{:>id_width$} {:pkg_width$} {:arch_width$} {:>time_width$} {:>time_width$} {:status_width$}",
+            "ID".bold(),
+            "Package".bold(),
+            "Arch".bold(),
+            "Updated".bold(),
+            "Time".bold(),
+            "Status".bold(),
+            id_width = max_id_len,
+            pkg_width = max_pkg_len,
+            arch_width = max_arch_len,
+            time_width = max_time_len,
+            status_width = max_status_len
+        );
+
+        // Print separator
+        println!(
+            "{:-<id_width$} {:-<pkg_width$} {:-<arch_width$} {:-<time_width$} {:-<time_width$} {:-<status_width$}",
+            "",
+            "",
+            "",
+            "",
+            "",
+            "",
+            id_width = max_id_len,
+            pkg_width = max_pkg_len,
+            arch_width = max_arch_len,
+            time_width = max_time_len,
+            status_width = max_status_len
+        );
+    }
+
+    // First print building items
+    for task in tasks
+        .iter()
+        .filter(|t| t.status == data::summit::BuildStatus::Building)
+    {
+        print_task(
+            task,
+            now,
+            name_limit,
+            log_base,
+            max_id_len,
+            max_pkg_len,
+            max_arch_len,
+            max_time_len,
+        );
+    }
+
+    // Then print new items
+    for task in tasks
+        .iter()
+        .filter(|t| t.status == data::summit::BuildStatus::New)
+    {
+        print_task(
+            task,
+            now,
+            name_limit,
+            log_base,
+            max_id_len,
+            max_pkg_len,
+            max_arch_len,
+            max_time_len,
+        );
+    }
+
+    // Finally print remaining items
+    for task in tasks.iter().filter(|t| {
+        !matches!(
+            t.status,
+            data::summit::BuildStatus::Building | data::summit::BuildStatus::New
+        )
+    }) {
+        print_task(
+            task,
+            now,
+            name_limit,
+            log_base,
+            max_id_len,
+            max_pkg_len,
+            max_arch_len,
+            max_time_len,
+        );
+    }
+
+    print_footer(global, tasks.len(), "tasks");
+}
+
+// This function prints a matrix of how many builds each architecture has in each
+// status, followed by the total number of failures
+pub fn print_build_summary(global: &GlobalArgs, tasks: &[data::summit::Task]) -> Result<(), Error> {
+    let mut counts = BTreeMap::<&str, BTreeMap<&str, usize>>::new();
+    for task in tasks {
+        let (label, _) = BUILD_STATUSES
+            .iter()
+            .find(|(_, status)| *status == task.status)
+            .expect("every status is listed");
+        *counts
+            .entry(&task.architecture)
+            .or_default()
+            .entry(label)
+            .or_default() += 1;
+    }
+
+    if global.format == Format::Json {
+        return print_json(global, &counts);
+    }
+
+    let arch_width = counts.keys().map(|a| a.len()).max().unwrap_or(0).max(4);
+    let count_width = |label: &str| label.len().max(5);
+    let failed = tasks
+        .iter()
+        .filter(|t| t.status == data::summit::BuildStatus::Failed)
+        .count();
+
+    if !global.no_header {
+        print!("\n{:arch_width$}", "Arch".bold());
+        for (label, _) in BUILD_STATUSES {
+            print!(" {:>width$}", label.bold(), width = count_width(label));
+        }
+        println!(" {:>5}", "total".bold());
+
+        print!("{:-<arch_width$}", "");
+        for (label, _) in BUILD_STATUSES {
+            print!(" {:-<width$}", "", width = count_width(label));
+        }
+        println!(" {:-<5}", "");
+    }
+
+    for (arch, statuses) in &counts {
+        print!("{:arch_width$}", arch.cyan());
+        for (label, _) in BUILD_STATUSES {
+            let count = statuses.get(label).copied().unwrap_or(0);
+            let cell = format!("{:>width$}", count, width = count_width(label));
+            match *label {
+                "failed" if count > 0 => print!(" {}", cell.red().bold()),
+                _ if count == 0 => print!(" {}", cell.dimmed()),
+                _ => print!(" {}", cell),
+            }
+        }
+        println!(" {:>5}", statuses.values().sum::<usize>());
+    }
+
+    let text = failed.to_string();
+    println!(
+        "\nTotal failed: {}",
+        if failed > 0 {
+            text.red().bold()
+        } else {
+            text.green().bold()
+        }
+    );
+
+    print_footer(global, tasks.len(), "tasks");
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn print_task(
+    task: &data::summit::Task,
+    now: i64,
+    name_limit: Option<usize>,
+    log_base: Option<&str>,
+    max_id_len: usize,
+    max_pkg_len: usize,
+    max_arch_len: usize,
+    max_time_len: usize,
+) {
+    let status_color = match task.status {
+        data::summit::BuildStatus::New => "cyan",
+        data::summit::BuildStatus::Failed => "red",
+        data::summit::BuildStatus::Building => "yellow",
+        data::summit::BuildStatus::Publishing => "blue",
+        data::summit::BuildStatus::Completed => "green",
+        data::summit::BuildStatus::Blocked => "red",
+    };
+
+    let truncated_build_id = match name_limit {
+        Some(limit) => truncate(task_name(task), limit),
+        None => task_name(task).to_string(),
+    };
+
+    // Link the build log at the end of the row if requested
+    let log = log_base
+        .map(|base| format!(" {}", task.log_url(base)))
+        .unwrap_or_default();
+
+    println!(
+        "{:>id_width$} {:<pkg_width$} {:<arch_width$} {:>time_width$} {:>time_width$} {:<status_width$}{}",
+        task.id.to_string().bold(),
+        truncated_build_id.cyan(),
+        task.architecture,
+        format_age(task.ts_updated, now),
+        task_elapsed(task, now),
+        format!("{:?}", task.status).color(status_color).bold(),
+        log.dimmed(),
+        id_width = max_id_len,
+        pkg_width = max_pkg_len,
+        arch_width = max_arch_len,
+        time_width = max_time_len,
+        status_width = if log_base.is_some() { 10 } else { 0 },
+    );
+}
+
+// This function returns the name a task is listed under, the last segment of its build id
+fn task_name(task: &data::summit::Task) -> &str {
+    task.build_id.rsplit('/').next().unwrap_or(&task.build_id)
+}
+
+// This function shortens text to at most `max` characters, ending it with an
+// ellipsis when anything was cut. Unlike byte slicing it is safe for any UTF-8.
+fn truncate(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let kept = s.chars().take(max.saturating_sub(3)).collect::<String>();
+    format!("{kept}...")
+}
+
+// This function formats a number of seconds as `mm:ss`, or `HhMm` from an hour up
+fn format_elapsed(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    if seconds < 60 * 60 {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h{}m", seconds / (60 * 60), seconds / 60 % 60)
+    }
+}
+
+// This function formats how long ago a timestamp was, in its largest whole unit
+fn format_age(timestamp: i64, now: i64) -> String {
+    if timestamp <= 0 {
+        return "-".to_string();
+    }
+    let seconds = now.saturating_sub(timestamp).max(0);
+    match seconds {
+        s if s < 60 => format!("{s}s ago"),
+        s if s < 60 * 60 => format!("{}m ago", s / 60),
+        s if s < 60 * 60 * 24 => format!("{}h ago", s / (60 * 60)),
+        s => format!("{}d ago", s / (60 * 60 * 24)),
+    }
+}
+
+// This function returns how long a task ran for, or has been running for when
+// it hasn't ended yet
+fn task_elapsed(task: &data::summit::Task, now: i64) -> String {
+    match (task.ts_started, task.ts_ended) {
+        (started, _) if started <= 0 => "-".to_string(),
+        (started, ended) if ended > 0 => format_elapsed(ended - started),
+        (started, _) => format_elapsed(now - started),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quotes_fields_with_commas() {
+        let updates = [RequiredUpdate {
+            source: "gst-plugins,good".to_string(),
+            current_version: "1.22.0".to_string(),
+            latest_version: "1.24.0 \"beta\"".to_string(),
+            project_id: 1263,
+            github: None,
+        }];
+        let mut out = vec![];
+        write_update_csv(&mut out, &updates).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "source,current_version,latest_version\n\
+             \"gst-plugins,good\",1.22.0,\"1.24.0 \"\"beta\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn update_json_always_has_the_envelope() {
+        let updates = [RequiredUpdate {
+            source: "nano".to_string(),
+            current_version: "7.2".to_string(),
+            latest_version: "8.0".to_string(),
+            project_id: 2046,
+            github: None,
+        }];
+
+        let clean = update_json(&updates, &[], false);
+        assert_eq!(clean["partial"], false);
+        assert_eq!(clean["errors"], 0);
+        assert_eq!(clean["updates"][0]["source"], "nano");
+        assert_eq!(clean["failures"], serde_json::json!([]));
+
+        let interrupted = update_json(&updates, &[], true);
+        assert_eq!(interrupted["partial"], true);
+        assert_eq!(interrupted["updates"], clean["updates"]);
+    }
+}
@@ -0,0 +1,198 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Conservative in-place edits of recipe files
+
+/// A rewrite of the `version` line of a recipe file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionEdit {
+    /// 1-based line number of the edited line
+    pub line: usize,
+    /// The line as it currently reads
+    pub before: String,
+    /// The line as it would read after the edit
+    pub after: String,
+}
+
+impl VersionEdit {
+    /// Apply the edit to the contents it was computed from
+    pub fn apply(&self, contents: &str) -> String {
+        contents
+            .split_inclusive('\n')
+            .enumerate()
+            .map(|(i, line)| {
+                if i + 1 == self.line {
+                    let ending = &line[line.trim_end_matches(['\r', '\n']).len()..];
+                    format!("{}{}", self.after, ending)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect()
+    }
+}
+
+// This function rejects versions that can't safely be rewritten, such as ones
+// pinned to a VCS snapshot or containing YAML syntax
+fn is_plain_version(version: &str) -> bool {
+    let lower = version.to_lowercase();
+    !version.is_empty()
+        && version.chars().any(|c| c.is_ascii_digit())
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        && !["git", "svn", "hg", "bzr", "cvs", "snapshot"]
+            .iter()
+            .any(|vcs| lower.contains(vcs))
+}
+
+/// Compute the edit replacing `current` with `latest` on the top level `version`
+/// line of a recipe
+///
+/// Only the version value itself is replaced, so quoting, spacing and trailing
+/// comments are kept. Nothing is suggested unless there is exactly one such line,
+/// its value is exactly `current`, and both versions are plain version strings.
+pub fn suggest_version(contents: &str, current: &str, latest: &str) -> Option<VersionEdit> {
+    if !is_plain_version(current) || !is_plain_version(latest) {
+        return None;
+    }
+
+    let mut candidates = contents.lines().enumerate().filter(|(_, line)| {
+        line.split_once(':')
+            .is_some_and(|(key, _)| key.trim_end() == "version")
+    });
+    let (index, line) = candidates.next()?;
+    if candidates.next().is_some() {
+        return None;
+    }
+
+    // Split the line into `version:` and the value, keeping the whitespace around it
+    let (key, value) = line.split_once(':')?;
+    let value_start = key.len() + 1 + (value.len() - value.trim_start().len());
+    let value = value.trim_start();
+
+    // Strip any quoting and trailing comment to find the bare version
+    let (bare, quote) = match value.chars().next()? {
+        q @ ('"' | '\'') => (value[1..].split(q).next()?, Some(q)),
+        _ => (value.split(" #").next()?.trim_end(), None),
+    };
+    if bare != current {
+        return None;
+    }
+
+    let offset = value_start + usize::from(quote.is_some());
+    let after = format!(
+        "{}{}{}",
+        &line[..offset],
+        latest,
+        &line[offset + bare.len()..]
+    );
+
+    Some(VersionEdit {
+        line: index + 1,
+        before: line.to_string(),
+        after,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // This function applies the suggested edit, if any, returning the new contents
+    fn edited(contents: &str, current: &str, latest: &str) -> Option<String> {
+        suggest_version(contents, current, latest).map(|edit| edit.apply(contents))
+    }
+
+    #[test]
+    fn keeps_quoting() {
+        assert_eq!(
+            edited("name: nano\nversion: \"7.2\"\n", "7.2", "8.0").as_deref(),
+            Some("name: nano\nversion: \"8.0\"\n")
+        );
+        assert_eq!(
+            edited("name: nano\nversion: '7.2'\n", "7.2", "8.0").as_deref(),
+            Some("name: nano\nversion: '8.0'\n")
+        );
+    }
+
+    #[test]
+    fn keeps_trailing_comments() {
+        let edit = suggest_version(
+            "version: 7.2 # keep in sync with nano-syntax\n",
+            "7.2",
+            "8.0",
+        );
+
+        assert_eq!(
+            edit,
+            Some(VersionEdit {
+                line: 1,
+                before: "version: 7.2 # keep in sync with nano-syntax".to_string(),
+                after: "version: 8.0 # keep in sync with nano-syntax".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_stone_alignment() {
+        let contents = "name        : nano\nversion     : 7.2\nrelease     : 1\n";
+
+        assert_eq!(
+            edited(contents, "7.2", "8.0").as_deref(),
+            Some("name        : nano\nversion     : 8.0\nrelease     : 1\n")
+        );
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let contents = "name: nano\r\nversion: 7.2\r\nrelease: 1\r\n";
+
+        assert_eq!(
+            edited(contents, "7.2", "8.0").as_deref(),
+            Some("name: nano\r\nversion: 8.0\r\nrelease: 1\r\n")
+        );
+    }
+
+    #[test]
+    fn ignores_nested_version_keys() {
+        let nested = "name: nano\nsource:\n  version: 7.2\n";
+        assert_eq!(suggest_version(nested, "7.2", "8.0"), None);
+
+        let contents = "version: 7.2\nsource:\n  version: 7.2\n";
+        assert_eq!(
+            edited(contents, "7.2", "8.0").as_deref(),
+            Some("version: 8.0\nsource:\n  version: 7.2\n")
+        );
+    }
+
+    #[test]
+    fn refuses_ambiguous_recipes() {
+        let contents = "version: 7.2\nversion: 7.2\n";
+
+        assert_eq!(suggest_version(contents, "7.2", "8.0"), None);
+    }
+
+    #[test]
+    fn refuses_vcs_versions() {
+        for (current, latest) in [
+            ("1.0+git20240101", "1.1"),
+            ("snapshot", "1.1"),
+            ("1.0", "1.1-git20240101"),
+        ] {
+            let contents = format!("version: {current}\n");
+            assert_eq!(
+                suggest_version(&contents, current, latest),
+                None,
+                "{current}"
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        assert_eq!(suggest_version("version: 7.1\n", "7.2", "8.0"), None);
+        assert_eq!(suggest_version("version: \"7.2.1\"\n", "7.2", "8.0"), None);
+    }
+}
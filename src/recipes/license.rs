@@ -0,0 +1,186 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Validation of SPDX license identifiers
+
+/// Commonly used SPDX license identifiers
+///
+/// This is deliberately not the full SPDX list. It covers the licenses seen in
+/// practice across recipe trees, so anything else is worth a second look. The
+/// deprecated GNU ids are included, as they are still common as `GPL-2.0+`.
+const KNOWN_IDS: &[&str] = &[
+    "0BSD",
+    "AFL-2.1",
+    "AFL-3.0",
+    "AGPL-3.0-only",
+    "AGPL-3.0-or-later",
+    "Apache-1.1",
+    "Apache-2.0",
+    "Artistic-1.0",
+    "Artistic-1.0-Perl",
+    "Artistic-2.0",
+    "BSD-1-Clause",
+    "BSD-2-Clause",
+    "BSD-2-Clause-Patent",
+    "BSD-3-Clause",
+    "BSD-3-Clause-Clear",
+    "BSD-4-Clause",
+    "BSL-1.0",
+    "CC-BY-3.0",
+    "CC-BY-4.0",
+    "CC-BY-SA-3.0",
+    "CC-BY-SA-4.0",
+    "CC0-1.0",
+    "CDDL-1.0",
+    "CDDL-1.1",
+    "CPL-1.0",
+    "curl",
+    "EPL-1.0",
+    "EPL-2.0",
+    "EUPL-1.2",
+    "FSFAP",
+    "FTL",
+    "GFDL-1.2-only",
+    "GFDL-1.2-or-later",
+    "GFDL-1.3-only",
+    "GFDL-1.3-or-later",
+    "GPL-1.0-only",
+    "GPL-1.0-or-later",
+    "GPL-2.0",
+    "GPL-2.0-only",
+    "GPL-2.0-or-later",
+    "GPL-3.0",
+    "GPL-3.0-only",
+    "GPL-3.0-or-later",
+    "HPND",
+    "ICU",
+    "IJG",
+    "ISC",
+    "LGPL-2.0-only",
+    "LGPL-2.0-or-later",
+    "LGPL-2.1",
+    "LGPL-2.1-only",
+    "LGPL-2.1-or-later",
+    "LGPL-3.0-only",
+    "LGPL-3.0-or-later",
+    "Libpng",
+    "libpng-2.0",
+    "libtiff",
+    "MIT",
+    "MIT-0",
+    "MPL-1.1",
+    "MPL-2.0",
+    "MPL-2.0-no-copyleft-exception",
+    "NCSA",
+    "OFL-1.1",
+    "OpenSSL",
+    "PHP-3.01",
+    "PostgreSQL",
+    "PSF-2.0",
+    "Python-2.0",
+    "Ruby",
+    "Sleepycat",
+    "Unicode-3.0",
+    "Unicode-DFS-2016",
+    "Unlicense",
+    "Vim",
+    "W3C",
+    "WTFPL",
+    "X11",
+    "Zlib",
+    "ZPL-2.1",
+];
+
+/// Commonly used SPDX license exceptions, for `WITH` clauses
+const KNOWN_EXCEPTIONS: &[&str] = &[
+    "Autoconf-exception-3.0",
+    "Bison-exception-2.2",
+    "Classpath-exception-2.0",
+    "GCC-exception-3.1",
+    "LLVM-exception",
+    "OpenSSL-exception",
+];
+
+// This function checks a single license identifier, allowing custom LicenseRef ids
+fn is_known_id(id: &str) -> bool {
+    let id = id.strip_suffix('+').unwrap_or(id);
+    id.starts_with("LicenseRef-") || KNOWN_IDS.iter().any(|known| known.eq_ignore_ascii_case(id))
+}
+
+/// Whether the license is a valid SPDX license expression, such as `MIT`,
+/// `GPL-2.0-or-later WITH Classpath-exception-2.0` or `(MIT OR Apache-2.0)`
+pub fn is_spdx_expression(license: &str) -> bool {
+    /// What the parser accepts next
+    enum Expect {
+        License,
+        Exception,
+        Operator { with_allowed: bool },
+    }
+
+    let spaced = license.replace('(', " ( ").replace(')', " ) ");
+    let mut depth = 0usize;
+    let mut expect = Expect::License;
+
+    for token in spaced.split_whitespace() {
+        expect = match (expect, token) {
+            (Expect::Exception, id) if KNOWN_EXCEPTIONS.contains(&id) => Expect::Operator {
+                with_allowed: false,
+            },
+            (Expect::License, "(") => {
+                depth += 1;
+                Expect::License
+            }
+            (Expect::License, id) if id != ")" && is_known_id(id) => {
+                Expect::Operator { with_allowed: true }
+            }
+            (Expect::Operator { .. }, ")") if depth > 0 => {
+                depth -= 1;
+                Expect::Operator {
+                    with_allowed: false,
+                }
+            }
+            (Expect::Operator { .. }, "AND" | "OR") => Expect::License,
+            (Expect::Operator { with_allowed: true }, "WITH") => Expect::Exception,
+            _ => return false,
+        };
+    }
+
+    matches!(expect, Expect::Operator { .. }) && depth == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_spdx_expressions() {
+        for license in [
+            "MIT",
+            "mit",
+            "GPL-2.0+",
+            "GPL-2.0-or-later WITH Classpath-exception-2.0",
+            "(MIT OR Apache-2.0)",
+            "(MIT OR Apache-2.0) AND BSD-3-Clause",
+            "LicenseRef-foo",
+        ] {
+            assert!(is_spdx_expression(license), "{license}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for license in [
+            "",
+            "(MIT OR Apache-2.0",
+            "MIT OR Apache-2.0)",
+            "MIT AND",
+            "(MIT OR GPL-2.0-only) WITH Classpath-exception-2.0",
+            "MIT WITH Unknown-exception",
+            "custom:BSD",
+            "Proprietary",
+        ] {
+            assert!(!is_spdx_expression(license), "{license}");
+        }
+    }
+}
@@ -4,12 +4,18 @@
 
 //! Recipe parsing and handling library
 
+use std::path::{Path, PathBuf};
+
+pub mod edit;
+pub mod license;
 mod monitoring;
 mod parser;
+pub mod scan;
 
-use monitoring::Monitoring;
+pub use monitoring::Monitoring;
 pub use parser::*;
 
+mod pkgbuild;
 mod stone;
 mod ypkg;
 
@@ -24,4 +30,48 @@ pub struct Recipe {
 
     // Monitoring data
     pub monitoring: Option<Monitoring>,
+
+    // Path of the recipe file this was parsed from
+    pub path: PathBuf,
+
+    // Upstream source URLs the recipe is built from
+    pub source_urls: Vec<String>,
+
+    // Declared licenses, ideally SPDX identifiers. Stone, ypkg and PKGBUILD recipes
+    // may all list several, so this is a list where none declared is empty.
+    pub licenses: Vec<String>,
+
+    // Upstream homepage, when the recipe declares one
+    pub homepage: Option<String>,
+}
+
+impl Recipe {
+    /// Trim the name and version of a freshly parsed recipe, rejecting recipes
+    /// where either is empty
+    pub fn validate(mut self, path: &Path) -> Result<Self, RecipeError> {
+        self.name = self.name.trim().to_string();
+        self.version = self.version.trim().to_string();
+
+        if self.name.is_empty() {
+            return Err(RecipeError::EmptyName(path.display().to_string()));
+        }
+        if self.version.is_empty() {
+            return Err(RecipeError::EmptyVersion(path.display().to_string()));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Normalize a recipe name to its logical source, dropping any trailing version
+/// so that parallel versioned packages such as `python3.11` and `python3.12` share
+/// the normalized name `python`
+pub fn normalized_name(name: &str) -> String {
+    let trimmed =
+        name.trim_end_matches(|c: char| c.is_ascii_digit() || matches!(c, '.' | '-' | '_'));
+    if trimmed.is_empty() {
+        name.to_lowercase()
+    } else {
+        trimmed.to_lowercase()
+    }
 }
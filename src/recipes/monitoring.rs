@@ -4,6 +4,8 @@
 use serde::Deserialize;
 use thiserror::Error;
 
+use crate::version::{Bump, Scheme};
+
 /// CPE ID
 #[derive(Debug, Deserialize)]
 pub struct CpeID {
@@ -19,6 +21,14 @@ pub struct CpeID {
 /// ```yaml
 /// releases:
 ///  id: 1234
+///  max_bump: minor
+///  scheme: semver
+///  version_prefix: v
+///  stable_only: true
+///  # Or, for recipes bundling several upstreams
+///  ids: [1234, 5678]
+///  # Alternatively, for projects not on release-monitoring.org
+///  github: owner/repo
 /// security:
 /// cpe:
 /// - vendor: "vendor"
@@ -27,16 +37,40 @@ pub struct CpeID {
 ///
 #[derive(Debug)]
 pub struct Monitoring {
-    /// Project ID on release-monitoring.org
+    /// Project ID on release-monitoring.org, the first of `project_ids` or 0 when
+    /// there is none
     pub project_id: i64,
 
+    /// All project IDs on release-monitoring.org, from `releases -> id` followed
+    /// by `releases -> ids`
+    pub project_ids: Vec<i64>,
+
+    /// Largest version bump to suggest automatically, from `releases -> max_bump`
+    pub max_bump: Option<Bump>,
+
+    /// Versioning scheme declared in `releases -> scheme`, overriding the CLI default
+    pub scheme: Option<Scheme>,
+
+    /// Prefix of upstream versions, such as `v`, from `releases -> version_prefix`
+    pub version_prefix: Option<String>,
+
+    /// Suffix of upstream versions, from `releases -> version_suffix`
+    pub version_suffix: Option<String>,
+
+    /// Only track versions upstream marks as stable, from `releases -> stable_only`
+    pub stable_only: bool,
+
+    /// GitHub repository publishing the releases, as `owner/repo`, from
+    /// `releases -> github`
+    pub github: Option<String>,
+
     /// Found in the `security -> cpe` part of monitorng YAML
     pub cpes: Vec<CpeID>,
 }
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("Error parsing monitoring YAML")]
+    #[error("Error parsing monitoring YAML: {0}")]
     ParseError(#[from] serde_yaml::Error),
 }
 
@@ -50,6 +84,14 @@ struct MonitoringYAML {
 #[derive(serde::Deserialize)]
 struct ReleasesYAML {
     pub id: Option<i64>,
+    pub ids: Option<Vec<i64>>,
+    pub max_bump: Option<Bump>,
+    pub scheme: Option<Scheme>,
+    pub version_prefix: Option<String>,
+    pub version_suffix: Option<String>,
+    #[serde(default)]
+    pub stable_only: bool,
+    pub github: Option<String>,
 }
 
 // This is the struct that represents the security part of the monitoring YAML
@@ -63,9 +105,88 @@ impl Monitoring {
     pub fn from_str(s: &str) -> Result<Self, Error> {
         let m: MonitoringYAML = serde_yaml::from_str(s).map_err(Error::ParseError)?;
 
-        let project_id = m.releases.and_then(|r| r.id).unwrap_or(0);
+        let mut project_ids = vec![];
+        if let Some(r) = &m.releases {
+            for &id in r.id.iter().chain(r.ids.iter().flatten()) {
+                if id != 0 && !project_ids.contains(&id) {
+                    project_ids.push(id);
+                }
+            }
+        }
+        let project_id = project_ids.first().copied().unwrap_or(0);
+        let max_bump = m.releases.as_ref().and_then(|r| r.max_bump);
+        let scheme = m.releases.as_ref().and_then(|r| r.scheme);
+        let stable_only = m.releases.as_ref().is_some_and(|r| r.stable_only);
+        let (version_prefix, version_suffix, github) = m
+            .releases
+            .map(|r| (r.version_prefix, r.version_suffix, r.github))
+            .unwrap_or_default();
         let cpes = m.security.and_then(|s| s.cpe).unwrap_or_default();
 
-        Ok(Monitoring { project_id, cpes })
+        Ok(Monitoring {
+            project_id,
+            project_ids,
+            max_bump,
+            scheme,
+            version_prefix,
+            version_suffix,
+            stable_only,
+            github,
+            cpes,
+        })
+    }
+
+    /// Whether upstream versions need a prefix or suffix stripped
+    pub fn has_version_affixes(&self) -> bool {
+        self.version_prefix.is_some() || self.version_suffix.is_some()
+    }
+
+    /// Strip the declared prefix and suffix from an upstream version, so that it
+    /// can be compared with the recipe version
+    pub fn strip_version<'a>(&self, version: &'a str) -> &'a str {
+        let version = match &self.version_prefix {
+            Some(prefix) => version.strip_prefix(prefix.as_str()).unwrap_or(version),
+            None => version,
+        };
+        match &self.version_suffix {
+            Some(suffix) => version.strip_suffix(suffix.as_str()).unwrap_or(version),
+            None => version,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_id_is_the_project_id() {
+        let m = Monitoring::from_str("releases:\n  id: 2046\n").unwrap();
+
+        assert_eq!(m.project_id, 2046);
+        assert_eq!(m.project_ids, [2046]);
+    }
+
+    #[test]
+    fn id_list_keeps_the_first_as_project_id() {
+        let m = Monitoring::from_str("releases:\n  ids: [5092, 4178]\n").unwrap();
+        assert_eq!(m.project_id, 5092);
+        assert_eq!(m.project_ids, [5092, 4178]);
+
+        // Both shapes may be combined, ignoring repeats and the placeholder 0
+        let m =
+            Monitoring::from_str("releases:\n  id: 2046\n  ids:\n    - 0\n    - 2046\n    - 7\n")
+                .unwrap();
+        assert_eq!(m.project_id, 2046);
+        assert_eq!(m.project_ids, [2046, 7]);
+    }
+
+    #[test]
+    fn missing_ids_are_zero() {
+        let m = Monitoring::from_str("releases:\n  github: vim/vim\n").unwrap();
+
+        assert_eq!(m.project_id, 0);
+        assert!(m.project_ids.is_empty());
+        assert_eq!(m.github.as_deref(), Some("vim/vim"));
     }
 }
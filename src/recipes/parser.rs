@@ -4,7 +4,10 @@
 
 //! API for recipe parsers exposed via `inventory` crate.
 
-use std::path::Path;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use thiserror::Error;
 
@@ -13,23 +16,85 @@ use super::{monitoring, Recipe};
 // This is the error type that all parsers must return
 #[derive(Debug, Error)]
 pub enum RecipeError {
-    #[error("Recipe is invalid {0}")]
+    #[error("Recipe is invalid: {0}")]
     InvalidRecipe(String),
 
     #[error("IO error: {0}")]
     IO(#[from] std::io::Error),
 
-    #[error("Monitoring data is invalid")]
-    InvalidMonitoring(#[from] monitoring::Error),
+    #[error("Monitoring data {1} is invalid: {0}")]
+    InvalidMonitoring(#[source] monitoring::Error, String),
 
     #[error("Recipe is unsupported")]
     UnsupportedRecipe,
+
+    #[error("Recipe has an empty name: {0}")]
+    EmptyName(String),
+
+    #[error("Recipe has an empty version: {0}")]
+    EmptyVersion(String),
+
+    #[error("Recipe {0} is defined more than once: {}", .1.join(", "))]
+    DuplicateName(String, Vec<String>),
+}
+
+// Options controlling how recipes are parsed
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    // How many parent directories to search for a monitoring file when the
+    // recipe directory has none
+    pub monitoring_depth: usize,
 }
 
 // This is the trait that all parsers must implement
 pub trait RecipeParser {
-    // This function is used to parse the recipe
-    fn parse(&self, recipe: &Path) -> Result<Recipe, RecipeError>;
+    // This function is used to parse the recipe file at the given path
+    fn parse(&self, recipe: &Path, options: &ParseOptions) -> Result<Recipe, RecipeError> {
+        let contents = read_normalized(recipe)?;
+        self.parse_contents(&contents, recipe, options)
+    }
+
+    // This function is used to parse recipe contents read from `recipe`. The path
+    // is only used to locate monitoring data and need not exist, so recipes can
+    // also be parsed from stdin.
+    fn parse_contents(
+        &self,
+        contents: &str,
+        recipe: &Path,
+        options: &ParseOptions,
+    ) -> Result<Recipe, RecipeError>;
+}
+
+// This function finds the monitoring file for a recipe. The recipe directory is
+// searched first, then up to `monitoring_depth` parent directories, so the
+// nearest file wins and a file next to the recipe always takes precedence over
+// one shared by several recipes higher up.
+pub(crate) fn find_monitoring(
+    recipe: &Path,
+    names: &[&str],
+    options: &ParseOptions,
+) -> Option<PathBuf> {
+    recipe
+        .parent()?
+        .ancestors()
+        .take(options.monitoring_depth + 1)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.exists())
+}
+
+// This function reads a recipe related file, normalizing Windows style line
+// endings so that parsers only ever have to deal with `\n`
+pub(crate) fn read_normalized(path: &Path) -> io::Result<String> {
+    fs::read_to_string(path).map(normalize_line_endings)
+}
+
+// This function normalizes Windows style line endings to `\n`
+pub fn normalize_line_endings(contents: String) -> String {
+    if contents.contains('\r') {
+        contents.replace("\r\n", "\n")
+    } else {
+        contents
+    }
 }
 
 // This is the registration struct for the parsers
@@ -47,3 +112,152 @@ pub struct ParserRegistration {
 }
 
 inventory::collect!(ParserRegistration);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recipes::scan::find_parser;
+
+    // This function locates a fixture checked in under tests/fixtures
+    fn fixture(path: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(path)
+    }
+
+    #[test]
+    fn crlf_fixtures_keep_their_line_endings() {
+        let contents = fs::read_to_string(fixture("crlf/nano/PKGBUILD")).unwrap();
+        assert!(contents.contains("\r\n"));
+    }
+
+    #[test]
+    fn parsers_read_crlf_recipes() {
+        let cases = [
+            ("pkgbuild", "crlf/nano/PKGBUILD", "nano", "7.2", 2046),
+            ("ypkg", "crlf/vim/package.yml", "vim", "9.1.0", 5092),
+        ];
+        for (parser, path, name, version, project_id) in cases {
+            let parser = (find_parser(parser).unwrap().parser)();
+            let recipe = parser
+                .parse(&fixture(path), &ParseOptions::default())
+                .unwrap();
+
+            assert_eq!(recipe.name, name);
+            assert_eq!(recipe.version, version);
+            assert_eq!(recipe.monitoring.unwrap().project_id, project_id);
+            assert!(recipe.source_urls.iter().all(|url| !url.contains('\r')));
+            assert!(recipe.licenses.iter().all(|l| !l.contains('\r')));
+        }
+    }
+
+    #[test]
+    fn parsers_read_homepage_and_licenses() {
+        let cases = [
+            (
+                "stone",
+                "recipes/nano/stone.yaml",
+                Some("https://www.nano-editor.org"),
+                &["GPL-3.0-or-later", "GFDL-1.2-or-later"][..],
+            ),
+            (
+                "ypkg",
+                "recipes/curl/package.yml",
+                None,
+                &["curl", "ISC"][..],
+            ),
+            (
+                "ypkg",
+                "crlf/vim/package.yml",
+                Some("https://www.vim.org"),
+                &["Vim"][..],
+            ),
+            (
+                "pkgbuild",
+                "crlf/nano/PKGBUILD",
+                Some("https://www.nano-editor.org"),
+                &["GPL-3.0-or-later"][..],
+            ),
+        ];
+        for (parser, path, homepage, licenses) in cases {
+            let parser = (find_parser(parser).unwrap().parser)();
+            let recipe = parser
+                .parse(&fixture(path), &ParseOptions::default())
+                .unwrap();
+
+            assert_eq!(recipe.homepage.as_deref(), homepage, "{path}");
+            assert_eq!(recipe.licenses, licenses, "{path}");
+        }
+    }
+
+    #[test]
+    fn normalize_line_endings_only_replaces_crlf() {
+        assert_eq!(normalize_line_endings("a\r\nb\r\n".to_string()), "a\nb\n");
+        assert_eq!(normalize_line_endings("a\rb\n".to_string()), "a\rb\n");
+    }
+
+    #[test]
+    fn malformed_recipes_report_their_path() {
+        let root = tempfile::tempdir().unwrap();
+        for (parser, file) in [("stone", "stone.yaml"), ("ypkg", "package.yml")] {
+            let dir = root.path().join(parser);
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join(file);
+            fs::write(&path, "name: [nano\nversion: {\n").unwrap();
+
+            let parser = (find_parser(parser).unwrap().parser)();
+            let err = parser.parse(&path, &ParseOptions::default()).unwrap_err();
+
+            assert!(
+                matches!(&err, RecipeError::InvalidRecipe(p) if *p == path.display().to_string())
+            );
+            assert!(err.to_string().contains(&path.display().to_string()));
+        }
+    }
+
+    #[test]
+    fn malformed_monitoring_reports_its_path() {
+        let root = tempfile::tempdir().unwrap();
+        let recipe = root.path().join("package.yml");
+        let monitoring = root.path().join("monitoring.yaml");
+        fs::write(&recipe, "name: nano\nversion: \"7.2\"\n").unwrap();
+        fs::write(&monitoring, "releases: [id: {\n").unwrap();
+
+        let parser = (find_parser("ypkg").unwrap().parser)();
+        let err = parser.parse(&recipe, &ParseOptions::default()).unwrap_err();
+
+        assert!(matches!(err, RecipeError::InvalidMonitoring(..)));
+        assert!(err.to_string().contains(&monitoring.display().to_string()));
+    }
+
+    #[test]
+    fn unreadable_recipes_are_io_errors() {
+        let root = tempfile::tempdir().unwrap();
+        let parser = (find_parser("stone").unwrap().parser)();
+
+        let missing = root.path().join("missing/stone.yaml");
+        let err = parser
+            .parse(&missing, &ParseOptions::default())
+            .unwrap_err();
+        assert!(matches!(err, RecipeError::IO(e) if e.kind() == io::ErrorKind::NotFound));
+
+        // A directory can't be read even with elevated permissions, unlike a chmod 000 file
+        let dir = root.path().join("nano/stone.yaml");
+        fs::create_dir_all(&dir).unwrap();
+        let err = parser.parse(&dir, &ParseOptions::default()).unwrap_err();
+        assert!(matches!(err, RecipeError::IO(_)));
+    }
+
+    #[test]
+    fn unreadable_monitoring_is_an_io_error() {
+        let root = tempfile::tempdir().unwrap();
+        let recipe = root.path().join("package.yml");
+        fs::write(&recipe, "name: nano\nversion: \"7.2\"\n").unwrap();
+        fs::create_dir(root.path().join("monitoring.yaml")).unwrap();
+
+        let parser = (find_parser("ypkg").unwrap().parser)();
+        let err = parser.parse(&recipe, &ParseOptions::default()).unwrap_err();
+
+        assert!(matches!(err, RecipeError::IO(_)));
+    }
+}
@@ -0,0 +1,309 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use std::{collections::HashMap, path::Path};
+
+use super::{
+    monitoring::Monitoring,
+    parser::{find_monitoring, read_normalized},
+    ParseOptions, ParserRegistration, Recipe, RecipeError, RecipeParser,
+};
+
+/// Parser implementation for Arch style PKGBUILD files
+///
+/// PKGBUILDs are bash scripts, so rather than interpreting them only the top level
+/// variable assignments are extracted. Scalars and arrays, quoting and expansion
+/// of previously assigned variables are understood, which covers the metadata of
+/// well formed PKGBUILDs.
+struct Parser {}
+
+/// Top level variables of a PKGBUILD, each holding its words
+type Variables = HashMap<String, Vec<String>>;
+
+impl RecipeParser for Parser {
+    /// Parses the contents of a PKGBUILD and returns a Recipe
+    ///
+    /// # Arguments
+    /// * `contents` - Contents of the PKGBUILD
+    /// * `recipe` - Path to the PKGBUILD the contents were read from
+    /// * `options` - Options controlling where monitoring data is looked for
+    ///
+    /// # Returns
+    /// * `Result<Recipe, RecipeError>` - Parsed Recipe or error if parsing fails
+    fn parse_contents(
+        &self,
+        contents: &str,
+        recipe: &Path,
+        options: &ParseOptions,
+    ) -> Result<Recipe, RecipeError> {
+        let variables = parse_variables(contents);
+        let invalid = || RecipeError::InvalidRecipe(recipe.display().to_string());
+        let first = |name: &str| variables.get(name).and_then(|v| v.first()).cloned();
+
+        // Split packages are named after their base, the first pkgname otherwise
+        let name = first("pkgbase")
+            .or_else(|| first("pkgname"))
+            .ok_or_else(invalid)?;
+        // The pkgrel is a packaging release, which isn't part of the upstream version
+        let version = first("pkgver").ok_or_else(invalid)?;
+
+        // Look for the nearest monitoring file, next to the recipe or in a parent
+        let monitoring = match find_monitoring(recipe, &["monitoring.yaml"], options) {
+            Some(path) => Some(
+                Monitoring::from_str(&read_normalized(&path)?)
+                    .map_err(|e| RecipeError::InvalidMonitoring(e, path.display().to_string()))?,
+            ),
+            None => None,
+        };
+
+        // Sources may be renamed as `name::url` and carry a VCS prefix as `git+url`
+        let source_urls = variables
+            .get("source")
+            .into_iter()
+            .flatten()
+            .map(|source| source.rsplit("::").next().unwrap_or(source))
+            .map(|url| match url.split_once('+') {
+                Some((vcs, url)) if !vcs.contains("://") => url,
+                _ => url,
+            })
+            .filter(|url| url.contains("://"))
+            .map(|url| url.to_string())
+            .collect();
+
+        Ok(Recipe {
+            name,
+            version,
+            monitoring,
+            path: recipe.to_path_buf(),
+            source_urls,
+            licenses: variables.get("license").cloned().unwrap_or_default(),
+            homepage: first("url").filter(|url| !url.is_empty()),
+        })
+    }
+}
+
+// This function extracts the top level `name=value` and `name=(values...)`
+// assignments. Indented lines belong to functions and are ignored.
+fn parse_variables(contents: &str) -> Variables {
+    let mut variables = Variables::new();
+    let mut lines = contents.lines();
+
+    while let Some(line) = lines.next() {
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        let is_identifier = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            continue;
+        }
+
+        let words = match value.strip_prefix('(') {
+            // Arrays may span several lines, up to the closing parenthesis
+            Some(rest) => {
+                let mut array = rest.to_string();
+                let end = loop {
+                    if let Some(end) = closing_paren(&array) {
+                        break end;
+                    }
+                    let Some(next) = lines.next() else {
+                        break array.len();
+                    };
+                    array.push('\n');
+                    array.push_str(next);
+                };
+                split_words(&array[..end])
+            }
+            None => split_words(value).into_iter().take(1).collect(),
+        };
+
+        let words = words.iter().map(|w| expand(w, &variables)).collect();
+        variables.insert(name.to_string(), words);
+    }
+
+    variables
+}
+
+// This function finds the closing parenthesis of an array's contents so far,
+// outside of any quotes or comments. Like in bash, a `#` only starts a comment at
+// the beginning of a word, so the one in `url#tag=v1` doesn't.
+fn closing_paren(array: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut comment = false;
+    let mut word_start = true;
+    for (i, c) in array.char_indices() {
+        match (quote, c) {
+            _ if comment => comment = c != '\n',
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '#') if word_start => comment = true,
+            (None, ')') => return Some(i),
+            _ => {}
+        }
+        word_start = quote.is_none() && c.is_whitespace();
+    }
+    None
+}
+
+// This function splits a value into shell words, removing quotes and comments
+fn split_words(value: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = None::<String>;
+    let mut quote = None;
+    let mut comment = false;
+
+    for c in value.chars() {
+        match (quote, c) {
+            _ if comment => comment = c != '\n',
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, '#') if word.is_none() => comment = true,
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+
+    words
+}
+
+// This function expands `$name` and `${name}` references to previously assigned
+// variables, leaving anything it doesn't understand untouched
+fn expand(word: &str, variables: &Variables) -> String {
+    let mut expanded = String::with_capacity(word.len());
+    let mut rest = word;
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+
+        match variables.get(name).and_then(|v| v.first()) {
+            Some(value) if !name.is_empty() => {
+                expanded.push_str(value);
+                rest = &after[len..];
+            }
+            _ => {
+                expanded.push('$');
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+
+    expanded
+}
+
+// Register the PKGBUILD parser with the inventory system
+inventory::submit! {
+    ParserRegistration {
+        name: "pkgbuild_recipe",
+        parser: || Box::new(Parser {}),
+        pattern: &["*/PKGBUILD"],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NANO: &str = r#"# Maintainer: Someone <someone@example.com>
+pkgname=nano
+pkgver=7.2
+pkgrel=1
+pkgdesc="Pico editor clone (with enhancements)"
+url='https://www.nano-editor.org'
+license=('custom:BSD (3 clause)')
+depends=('ncurses') # provides libncursesw.so (wide)
+source=("https://www.nano-editor.org/dist/v7/$pkgname-$pkgver.tar.xz"
+        "$pkgname.desktop::https://example.com/nano (desktop).desktop" # a comment
+        git+https://git.savannah.gnu.org/git/nano.git#tag=v${pkgver})
+sha256sums=('SKIP')
+
+build() {
+  url=ignored
+  make
+}
+"#;
+
+    const SPLIT: &str = r#"pkgbase=python-foo
+pkgname=('python-foo' 'python-foo-docs')
+pkgver="1.0.0"
+source=('https://example.com/foo-1.0.0.tar.gz')
+"#;
+
+    // This function parses PKGBUILD contents from a directory without monitoring data
+    fn parse(contents: &str) -> Result<Recipe, RecipeError> {
+        let path = Path::new("/nonexistent/recipe/PKGBUILD");
+        Parser {}.parse_contents(contents, path, &ParseOptions::default())
+    }
+
+    #[test]
+    fn parses_quoted_and_array_values() {
+        let recipe = parse(NANO).unwrap();
+
+        assert_eq!(recipe.name, "nano");
+        assert_eq!(recipe.version, "7.2");
+        assert_eq!(
+            recipe.homepage.as_deref(),
+            Some("https://www.nano-editor.org")
+        );
+        assert_eq!(recipe.licenses, ["custom:BSD (3 clause)"]);
+        assert_eq!(
+            recipe.source_urls,
+            [
+                "https://www.nano-editor.org/dist/v7/nano-7.2.tar.xz",
+                "https://example.com/nano (desktop).desktop",
+                "https://git.savannah.gnu.org/git/nano.git#tag=v7.2",
+            ]
+        );
+    }
+
+    #[test]
+    fn arrays_end_at_their_own_parenthesis() {
+        let variables = parse_variables(NANO);
+
+        assert_eq!(variables["depends"], ["ncurses"]);
+        assert_eq!(variables["sha256sums"], ["SKIP"]);
+        // Indented assignments belong to functions
+        assert_eq!(variables["url"], ["https://www.nano-editor.org"]);
+    }
+
+    #[test]
+    fn split_packages_are_named_after_their_base() {
+        let recipe = parse(SPLIT).unwrap();
+
+        assert_eq!(recipe.name, "python-foo");
+        assert_eq!(recipe.version, "1.0.0");
+        assert_eq!(recipe.source_urls, ["https://example.com/foo-1.0.0.tar.gz"]);
+    }
+
+    #[test]
+    fn pkgrel_is_optional() {
+        assert!(parse(SPLIT).is_ok());
+        assert!(matches!(
+            parse("pkgname=foo\n"),
+            Err(RecipeError::InvalidRecipe(_))
+        ));
+    }
+}
@@ -0,0 +1,519 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Scanning a recipe tree for the recipes of every registered parser
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use glob::Pattern;
+use rayon::prelude::*;
+
+use super::{ParseOptions, ParserRegistration, Recipe, RecipeError};
+
+/// Directories that never contain recipes worth scanning
+pub const DEFAULT_IGNORES: &[&str] = &[".git", ".svn", "target", "node_modules"];
+
+/// Options for `scan`
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// How each recipe found is parsed
+    pub parse: ParseOptions,
+
+    /// Overrides of the filename patterns of the registered parsers
+    pub recipe_globs: Vec<RecipeGlob>,
+
+    /// Directories not to descend into, in addition to `DEFAULT_IGNORES`
+    pub ignore: Vec<Pattern>,
+
+    /// Also descend into hidden directories, whose name starts with a `.`
+    pub hidden: bool,
+
+    /// Only keep recipes whose path matches one of these, unless there are none
+    pub include_paths: Vec<regex::Regex>,
+
+    /// Drop recipes whose path matches one of these, even if included
+    pub exclude_paths: Vec<regex::Regex>,
+
+    /// Fail on malformed and duplicate recipes rather than skipping or reporting them
+    pub strict: bool,
+}
+
+/// The outcome of `scan`
+#[derive(Debug, Default)]
+pub struct Scan {
+    /// The recipes found, in no particular order
+    pub recipes: Vec<Recipe>,
+
+    /// Why recipes were skipped as malformed, which fails the scan in strict mode
+    pub skipped: Vec<RecipeError>,
+
+    /// Names shared by several recipes, which fails the scan in strict mode
+    pub duplicates: Vec<RecipeError>,
+}
+
+/// An override of a parser's filename patterns, given as `PARSER=GLOB` to replace
+/// the built-in patterns or `PARSER+=GLOB` to add to them
+#[derive(Clone, Debug)]
+pub struct RecipeGlob {
+    parser: String,
+    patterns: Vec<Pattern>,
+    // Whether the patterns are added to the built-in ones rather than replacing them
+    augment: bool,
+}
+
+impl FromStr for RecipeGlob {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((parser, glob)) = s.split_once('=') else {
+            return Err(format!("expected PARSER=GLOB, got `{s}`"));
+        };
+        let (parser, augment) = match parser.strip_suffix('+') {
+            Some(parser) => (parser, true),
+            None => (parser, false),
+        };
+        let parser = find_parser(parser)?.name;
+
+        let patterns = expand_braces(glob)
+            .iter()
+            .map(|p| Pattern::new(p).map_err(|e| format!("invalid glob `{glob}`: {e}")))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            parser: parser.to_string(),
+            patterns,
+            augment,
+        })
+    }
+}
+
+/// Directories skipped while scanning for recipes
+struct Ignores {
+    patterns: Vec<Pattern>,
+    // Whether hidden directories are descended into
+    hidden: bool,
+}
+
+impl Ignores {
+    /// The directories to skip as selected by the options
+    fn new(options: &ScanOptions) -> Self {
+        let defaults = DEFAULT_IGNORES.iter().map(|s| Pattern::new(s).unwrap());
+        Self {
+            patterns: defaults.chain(options.ignore.iter().cloned()).collect(),
+            hidden: options.hidden,
+        }
+    }
+
+    /// Whether the directory with the given name is skipped
+    fn skips(&self, name: &str) -> bool {
+        (!self.hidden && name.starts_with('.')) || self.patterns.iter().any(|p| p.matches(name))
+    }
+}
+
+/// Scan the recipes under the root of the tree with every registered parser
+///
+/// Recipes are narrowed down by their path before they are parsed, keeping those
+/// matching any of the include patterns (or all when there are none), unless they
+/// also match one of the exclude patterns, which always take precedence.
+///
+/// Recipes with an empty name or version, or with malformed monitoring data, are
+/// an error in strict mode, and are otherwise skipped. Likewise several recipes
+/// sharing a name are an error in strict mode, and otherwise only reported.
+pub fn scan(root: impl AsRef<Path>, options: &ScanOptions) -> Result<Scan, RecipeError> {
+    let registry = inventory::iter::<ParserRegistration>
+        .into_iter()
+        .map(|p| (p.name, p))
+        .collect::<HashMap<_, _>>();
+
+    // Apply any recipe glob overrides on top of the built-in patterns
+    let glob_patterns = registry
+        .values()
+        .flat_map(|p| {
+            let overrides = options
+                .recipe_globs
+                .iter()
+                .filter(move |g| g.parser == p.name);
+            let builtin = if overrides.clone().any(|g| !g.augment) {
+                vec![]
+            } else {
+                p.pattern
+                    .iter()
+                    .flat_map(|s| expand_braces(s))
+                    .map(|s| Pattern::new(&s).unwrap())
+                    .collect()
+            };
+            builtin
+                .into_iter()
+                .chain(overrides.flat_map(|g| g.patterns.iter().cloned()))
+                .map(move |pattern| (pattern, *p))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut found = vec![];
+    scan_dir(root, &glob_patterns, &Ignores::new(options), &mut found)?;
+    found.retain(|(path, _)| included(path, options));
+
+    // Parse the recipes in parallel on the rayon pool
+    let parsed = found
+        .par_iter()
+        .map(|(path, registration)| {
+            tracing::debug!(path = %path.display(), parser = registration.name, "scanning recipe");
+            let parser = (registration.parser)();
+            parser
+                .parse(path, &options.parse)
+                .and_then(|r| r.validate(path))
+        })
+        .collect::<Vec<_>>();
+
+    let mut scan = Scan::default();
+    for result in parsed {
+        match result {
+            Ok(r) => scan.recipes.push(r),
+            Err(
+                e @ (RecipeError::EmptyName(_)
+                | RecipeError::EmptyVersion(_)
+                | RecipeError::InvalidMonitoring(..)),
+            ) if !options.strict => {
+                tracing::warn!("skipping recipe: {e}");
+                scan.skipped.push(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    // Several recipes with one name are most likely a copy-paste mistake
+    let mut paths = BTreeMap::<&str, Vec<String>>::new();
+    for recipe in &scan.recipes {
+        paths
+            .entry(&recipe.name)
+            .or_default()
+            .push(recipe.path.display().to_string());
+    }
+    let mut duplicates = vec![];
+    for (name, mut paths) in paths.into_iter().filter(|(_, p)| p.len() > 1) {
+        paths.sort();
+        let e = RecipeError::DuplicateName(name.to_string(), paths);
+        if options.strict {
+            return Err(e);
+        }
+        tracing::warn!("{e}");
+        duplicates.push(e);
+    }
+    scan.duplicates = duplicates;
+
+    Ok(scan)
+}
+
+// This function checks whether a recipe path passes the include and exclude
+// patterns of the options
+fn included(path: &Path, options: &ScanOptions) -> bool {
+    let path = path.to_string_lossy();
+    let included = options.include_paths.is_empty()
+        || options.include_paths.iter().any(|re| re.is_match(&path));
+    let excluded = options.exclude_paths.iter().any(|re| re.is_match(&path));
+    included && !excluded
+}
+
+// This function scans the directory for files matching a parser's patterns,
+// skipping ignored directories
+fn scan_dir(
+    root: impl AsRef<Path>,
+    globs: &HashMap<Pattern, &'static ParserRegistration>,
+    ignores: &Ignores,
+    found: &mut Vec<(PathBuf, &'static ParserRegistration)>,
+) -> Result<(), RecipeError> {
+    let root = root.as_ref();
+
+    for entry in root.read_dir()?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if !ignores.skips(&entry.file_name().to_string_lossy()) {
+                scan_dir(&path, globs, ignores, found)?;
+            }
+        } else {
+            for (pattern, parser) in globs {
+                if pattern.matches_path(&path) {
+                    found.push((path.clone(), *parser));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Expand `{a,b}` style alternatives in a glob pattern into individual patterns,
+/// as the glob crate doesn't support brace expansion
+pub fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+
+    // Find the matching closing brace and the top-level commas within it
+    let mut depth = 0;
+    let mut close = None;
+    let mut commas = vec![];
+    for (i, c) in pattern[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(open + i);
+                    break;
+                }
+            }
+            ',' if depth == 1 => commas.push(open + i),
+            _ => {}
+        }
+    }
+
+    // Unbalanced braces are left for the glob crate to deal with
+    let Some(close) = close else {
+        return vec![pattern.to_string()];
+    };
+
+    let prefix = &pattern[..open];
+    let suffix = &pattern[close + 1..];
+    let mut start = open + 1;
+    let mut ret = vec![];
+    for end in commas.into_iter().chain(std::iter::once(close)) {
+        let alternative = &pattern[start..end];
+        ret.extend(expand_braces(&format!("{prefix}{alternative}{suffix}")));
+        start = end + 1;
+    }
+
+    ret
+}
+
+/// Find a registered recipe parser by its name, also accepting the name without
+/// its `_recipe` suffix, e.g. `ypkg` for `ypkg_recipe`
+pub fn find_parser(name: &str) -> Result<&'static ParserRegistration, String> {
+    let mut registry = inventory::iter::<ParserRegistration>.into_iter();
+    registry
+        .find(|p| p.name == name || p.name.strip_suffix("_recipe") == Some(name))
+        .ok_or_else(|| {
+            let known = inventory::iter::<ParserRegistration>
+                .into_iter()
+                .map(|p| p.name)
+                .collect::<Vec<_>>();
+            format!(
+                "unknown parser `{name}`, expected one of: {}",
+                known.join(", ")
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    // This function writes a ypkg recipe, and its monitoring data if given, into the tree
+    fn write_recipe(root: &Path, dir: &str, name: &str, monitoring: Option<&str>) {
+        let dir = root.join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("package.yml"),
+            format!("name: {name}\nversion: \"1.0\"\n"),
+        )
+        .unwrap();
+        if let Some(monitoring) = monitoring {
+            fs::write(dir.join("monitoring.yaml"), monitoring).unwrap();
+        }
+    }
+
+    #[test]
+    fn scan_skips_malformed_monitoring() {
+        let root = tempfile::tempdir().unwrap();
+        write_recipe(root.path(), "nano", "nano", Some("releases:\n  id: 2046\n"));
+        write_recipe(root.path(), "vim", "vim", Some("releases: [id: {\n"));
+
+        let scan = scan(root.path(), &ScanOptions::default()).unwrap();
+
+        assert_eq!(scan.recipes.len(), 1);
+        assert_eq!(scan.recipes[0].name, "nano");
+        assert_eq!(scan.skipped.len(), 1);
+        assert!(matches!(
+            &scan.skipped[0],
+            RecipeError::InvalidMonitoring(_, path) if path.ends_with("monitoring.yaml")
+        ));
+    }
+
+    #[test]
+    fn strict_scan_fails_on_malformed_monitoring() {
+        let root = tempfile::tempdir().unwrap();
+        write_recipe(root.path(), "nano", "nano", Some("releases:\n  id: 2046\n"));
+        write_recipe(root.path(), "vim", "vim", Some("releases: [id: {\n"));
+
+        let options = ScanOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let result = scan(root.path(), &options);
+
+        assert!(matches!(result, Err(RecipeError::InvalidMonitoring(..))));
+    }
+
+    #[test]
+    fn expand_braces_expands_alternatives() {
+        assert_eq!(
+            expand_braces("*/stone.{yml,yaml}"),
+            ["*/stone.yml", "*/stone.yaml"]
+        );
+        assert_eq!(expand_braces("{a,b}/{c,d}"), ["a/c", "a/d", "b/c", "b/d"]);
+        assert_eq!(expand_braces("{a,{b,c}}.yml"), ["a.yml", "b.yml", "c.yml"]);
+        assert_eq!(expand_braces("*/PKGBUILD"), ["*/PKGBUILD"]);
+        // Unbalanced braces are left alone
+        assert_eq!(expand_braces("*/stone.{yml"), ["*/stone.{yml"]);
+    }
+
+    #[test]
+    fn braced_recipe_glob_matches_every_alternative() {
+        let root = tempfile::tempdir().unwrap();
+        write_recipe(root.path(), "nano", "nano", None);
+        let vim = root.path().join("vim");
+        fs::create_dir(&vim).unwrap();
+        fs::write(vim.join("package.yaml"), "name: vim\nversion: \"1.0\"\n").unwrap();
+
+        let options = ScanOptions {
+            recipe_globs: vec!["ypkg=*/package.{yml,yaml}".parse().unwrap()],
+            ..Default::default()
+        };
+        let mut names = scan(root.path(), &options)
+            .unwrap()
+            .recipes
+            .into_iter()
+            .map(|r| r.name)
+            .collect::<Vec<_>>();
+        names.sort();
+
+        assert_eq!(names, ["nano", "vim"]);
+    }
+
+    #[test]
+    fn scan_skips_vcs_and_ignored_directories() {
+        let root = tempfile::tempdir().unwrap();
+        write_recipe(root.path(), "nano", "nano", None);
+        write_recipe(root.path(), ".git/objects", "git-object", None);
+        write_recipe(root.path(), "nano/target", "build-artifact", None);
+        write_recipe(root.path(), ".cache/vim", "vim", None);
+        write_recipe(root.path(), "vendor-old/curl", "curl", None);
+
+        let names = |options: &ScanOptions| {
+            let mut names = scan(root.path(), options)
+                .unwrap()
+                .recipes
+                .into_iter()
+                .map(|r| r.name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(&ScanOptions::default()), ["curl", "nano"]);
+
+        // Hidden directories may be included, but `.git` stays ignored
+        let options = ScanOptions {
+            ignore: vec![Pattern::new("vendor-*").unwrap()],
+            hidden: true,
+            ..Default::default()
+        };
+        assert_eq!(names(&options), ["nano", "vim"]);
+    }
+
+    #[test]
+    fn scan_reports_duplicate_names() {
+        let root = tempfile::tempdir().unwrap();
+        write_recipe(root.path(), "nano", "nano", None);
+        write_recipe(root.path(), "vim", "vim", None);
+        write_recipe(root.path(), "editors/vim", "vim", None);
+
+        let scan = scan(root.path(), &ScanOptions::default()).unwrap();
+
+        // Both recipes are kept, with the conflict reported alongside
+        assert_eq!(scan.recipes.len(), 3);
+        assert_eq!(scan.duplicates.len(), 1);
+        let RecipeError::DuplicateName(name, paths) = &scan.duplicates[0] else {
+            panic!("expected a duplicate name, got {:?}", scan.duplicates[0]);
+        };
+        assert_eq!(name, "vim");
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].ends_with("editors/vim/package.yml"));
+        assert!(paths[1].ends_with("vim/package.yml"));
+    }
+
+    #[test]
+    fn strict_scan_fails_on_duplicate_names() {
+        let root = tempfile::tempdir().unwrap();
+        write_recipe(root.path(), "vim", "vim", None);
+        write_recipe(root.path(), "editors/vim", "vim", None);
+
+        let options = ScanOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let result = scan(root.path(), &options);
+
+        assert!(matches!(result, Err(RecipeError::DuplicateName(name, _)) if name == "vim"));
+    }
+
+    // This function writes a ypkg recipe with the given contents into the tree
+    fn write_raw_recipe(root: &Path, dir: &str, contents: &str) {
+        let dir = root.join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("package.yml"), contents).unwrap();
+    }
+
+    #[test]
+    fn scan_skips_recipes_without_name_or_version() {
+        let root = tempfile::tempdir().unwrap();
+        write_recipe(root.path(), "nano", "nano", None);
+        write_raw_recipe(root.path(), "blank-version", "name: vim\nversion: \"  \"\n");
+        write_raw_recipe(root.path(), "blank-name", "name: \"\"\nversion: \"1.0\"\n");
+
+        let scan = scan(root.path(), &ScanOptions::default()).unwrap();
+
+        assert_eq!(scan.recipes.len(), 1);
+        assert_eq!(scan.recipes[0].name, "nano");
+        let mut skipped = scan
+            .skipped
+            .iter()
+            .map(|e| match e {
+                RecipeError::EmptyName(path) => ("name", path.clone()),
+                RecipeError::EmptyVersion(path) => ("version", path.clone()),
+                e => panic!("unexpected {e:?}"),
+            })
+            .collect::<Vec<_>>();
+        skipped.sort();
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(skipped[0].0, "name");
+        assert!(skipped[0].1.ends_with("blank-name/package.yml"));
+        assert_eq!(skipped[1].0, "version");
+        assert!(skipped[1].1.ends_with("blank-version/package.yml"));
+    }
+
+    #[test]
+    fn strict_scan_fails_on_recipes_without_name_or_version() {
+        let options = ScanOptions {
+            strict: true,
+            ..Default::default()
+        };
+
+        let root = tempfile::tempdir().unwrap();
+        write_raw_recipe(root.path(), "vim", "name: vim\nversion: \"  \"\n");
+        let result = scan(root.path(), &options);
+        assert!(matches!(result, Err(RecipeError::EmptyVersion(_))));
+
+        let root = tempfile::tempdir().unwrap();
+        write_raw_recipe(root.path(), "vim", "name: \"\"\nversion: \"1.0\"\n");
+        let result = scan(root.path(), &options);
+        assert!(matches!(result, Err(RecipeError::EmptyName(_))));
+    }
+}
@@ -2,8 +2,12 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use super::{monitoring::Monitoring, ParserRegistration, Recipe, RecipeError, RecipeParser};
-use std::{fs, path::Path};
+use super::{
+    monitoring::Monitoring,
+    parser::{find_monitoring, read_normalized},
+    ParseOptions, ParserRegistration, Recipe, RecipeError, RecipeParser,
+};
+use std::path::Path;
 
 /// A parser implementation for stone recipe files that parses recipe files and any associated
 /// monitoring configuration.
@@ -11,11 +15,13 @@ use std::{fs, path::Path};
 struct Parser {}
 
 impl RecipeParser for Parser {
-    /// Parses a stone recipe file and its optional monitoring configuration.
+    /// Parses the contents of a stone recipe file and its optional monitoring configuration.
     ///
     /// # Arguments
     ///
-    /// * `recipe` - Path to the stone recipe file to parse
+    /// * `contents` - Contents of the stone recipe file
+    /// * `recipe` - Path to the stone recipe file the contents were read from
+    /// * `options` - Options controlling where monitoring data is looked for
     ///
     /// # Returns
     ///
@@ -26,35 +32,47 @@ impl RecipeParser for Parser {
     /// # Notes
     ///
     /// The parser will look for an additional monitoring.yaml file in the same directory
-    /// as the recipe file. If found, its contents will be parsed and included in the
-    /// resulting Recipe struct.
-    fn parse(&self, recipe: &Path) -> Result<Recipe, RecipeError> {
+    /// as the recipe file, then in up to `options.monitoring_depth` parent directories.
+    /// If found, the nearest one will be parsed and included in the resulting Recipe struct.
+    fn parse_contents(
+        &self,
+        contents: &str,
+        recipe: &Path,
+        options: &ParseOptions,
+    ) -> Result<Recipe, RecipeError> {
         // Parse the main recipe file
-        let recipe_contents = fs::read_to_string(recipe)
-            .map_err(|_| {
-                RecipeError::InvalidRecipe(recipe.to_str().unwrap_or_default().to_string())
-            })
-            .unwrap_or_default();
-
-        let parsed_recipe = stone_recipe::from_str(&recipe_contents).map_err(|_| {
-            RecipeError::InvalidRecipe(recipe.to_str().unwrap_or_default().to_string())
-        })?;
+        let parsed_recipe = stone_recipe::from_str(contents)
+            .map_err(|_| RecipeError::InvalidRecipe(recipe.display().to_string()))?;
 
         // Check for and parse optional monitoring config
-        let adjacent_monitor = recipe.with_file_name("monitoring.yaml");
-        let monitoring = if adjacent_monitor.exists() {
-            let monitoring_contents = fs::read_to_string(&adjacent_monitor)
-                .map_err(|_| RecipeError::InvalidRecipe(adjacent_monitor.display().to_string()))
-                .unwrap_or_default();
-            Some(Monitoring::from_str(&monitoring_contents)?)
-        } else {
-            None
+        let monitoring = match find_monitoring(recipe, &["monitoring.yaml"], options) {
+            Some(adjacent_monitor) => {
+                let monitoring_contents = read_normalized(&adjacent_monitor)?;
+                let monitoring = Monitoring::from_str(&monitoring_contents).map_err(|e| {
+                    RecipeError::InvalidMonitoring(e, adjacent_monitor.display().to_string())
+                })?;
+                Some(monitoring)
+            }
+            None => None,
         };
 
+        let source_urls = parsed_recipe
+            .upstreams
+            .iter()
+            .map(|u| match u {
+                stone_recipe::Upstream::Plain { uri, .. } => uri.to_string(),
+                stone_recipe::Upstream::Git { uri, .. } => uri.to_string(),
+            })
+            .collect();
+
         Ok(Recipe {
             name: parsed_recipe.source.name,
             version: parsed_recipe.source.version,
             monitoring,
+            path: recipe.to_path_buf(),
+            source_urls,
+            licenses: parsed_recipe.source.license,
+            homepage: Some(parsed_recipe.source.homepage).filter(|h| !h.is_empty()),
         })
     }
 }
@@ -2,9 +2,13 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use std::{fs, path::Path};
+use std::{collections::BTreeMap, path::Path};
 
-use super::{monitoring::Monitoring, ParserRegistration, Recipe, RecipeError, RecipeParser};
+use super::{
+    monitoring::Monitoring,
+    parser::{find_monitoring, read_normalized},
+    ParseOptions, ParserRegistration, Recipe, RecipeError, RecipeParser,
+};
 
 /// Parser implementation for YPKG recipe files
 struct Parser {}
@@ -16,44 +20,87 @@ struct YpkgRecipe {
     name: String,
     /// Package version
     version: String,
+    /// Sources, each a single entry map of URL to hash or git ref
+    #[serde(default)]
+    source: Vec<BTreeMap<String, serde_yaml::Value>>,
+    /// Licenses, either a single identifier or a list of them
+    #[serde(default)]
+    license: License,
+    /// Upstream homepage
+    homepage: Option<String>,
+}
+
+/// The `license` key, which may be given as a string or a list
+#[derive(Default, serde::Deserialize)]
+#[serde(untagged)]
+enum License {
+    #[default]
+    None,
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl From<License> for Vec<String> {
+    fn from(value: License) -> Self {
+        match value {
+            License::None => vec![],
+            License::Single(license) => vec![license],
+            License::Multiple(licenses) => licenses,
+        }
+    }
 }
 
 impl RecipeParser for Parser {
-    /// Parses a YPKG recipe file at the given path and returns a Recipe
+    /// Parses the contents of a YPKG recipe file and returns a Recipe
     ///
     /// # Arguments
-    /// * `recipe` - Path to the YPKG recipe file to parse
+    /// * `contents` - Contents of the YPKG recipe file
+    /// * `recipe` - Path to the YPKG recipe file the contents were read from
+    /// * `options` - Options controlling where monitoring data is looked for
     ///
     /// # Returns
     /// * `Result<Recipe, RecipeError>` - Parsed Recipe or error if parsing fails
-    fn parse(&self, recipe: &Path) -> Result<Recipe, RecipeError> {
-        // Read and parse main recipe file
-        let s = fs::read_to_string(recipe)
+    fn parse_contents(
+        &self,
+        contents: &str,
+        recipe: &Path,
+        options: &ParseOptions,
+    ) -> Result<Recipe, RecipeError> {
+        // Parse main recipe file
+        let p: YpkgRecipe = serde_yaml::from_str(contents)
             .map_err(|_| RecipeError::InvalidRecipe(recipe.display().to_string()))?;
 
-        let p: YpkgRecipe = serde_yaml::from_str(&s)
-            .map_err(|_| RecipeError::InvalidRecipe(recipe.display().to_string()))?;
-
-        // Look for adjacent monitoring file
-        let adjacent_monitor = ["monitoring.yaml", "monitoring.yml"]
-            .iter()
-            .map(|name| recipe.with_file_name(name))
-            .find(|path| path.exists());
+        // Look for the nearest monitoring file, next to the recipe or in a parent
+        let adjacent_monitor =
+            find_monitoring(recipe, &["monitoring.yaml", "monitoring.yml"], options);
 
         // Parse monitoring file if it exists
         let monitoring = match adjacent_monitor {
             Some(path) => {
-                let s = fs::read_to_string(&path)
-                    .map_err(|_| RecipeError::InvalidRecipe(path.display().to_string()))?;
-                Monitoring::from_str(&s).ok()
+                let s = read_normalized(&path)?;
+                let monitoring = Monitoring::from_str(&s)
+                    .map_err(|e| RecipeError::InvalidMonitoring(e, path.display().to_string()))?;
+                Some(monitoring)
             }
             None => None,
         };
 
+        // Git sources are spelled as `git|https://...`
+        let source_urls = p
+            .source
+            .iter()
+            .flat_map(|s| s.keys())
+            .map(|url| url.strip_prefix("git|").unwrap_or(url).to_string())
+            .collect();
+
         Ok(Recipe {
             name: p.name,
             version: p.version,
             monitoring,
+            path: recipe.to_path_buf(),
+            source_urls,
+            licenses: p.license.into(),
+            homepage: p.homepage.filter(|h| !h.is_empty()),
         })
     }
 }
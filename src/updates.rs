@@ -0,0 +1,894 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Finding recipes that are behind their upstream release
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::StreamExt;
+use thiserror::Error;
+use tokio::sync::Notify;
+
+use crate::{
+    data::{
+        self,
+        cache::{self, VersionCache},
+        updates::VersionResponse,
+        Client,
+    },
+    recipes::{self, scan::ScanOptions, Monitoring, Recipe, RecipeError},
+    version::{self, Bump, Scheme},
+};
+
+/// Errors returned by `check`
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{0}")]
+    Recipe(#[from] RecipeError),
+
+    #[error("{0}")]
+    Data(#[from] data::Error),
+}
+
+/// Release channels to select upstream versions from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Channel {
+    /// Prefer stable releases, falling back to the latest available
+    #[default]
+    Latest,
+    /// Only consider stable releases
+    Stable,
+}
+
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latest" => Ok(Channel::Latest),
+            "stable" => Ok(Channel::Stable),
+            _ => Err(format!(
+                "unknown release channel `{s}`, expected latest or stable"
+            )),
+        }
+    }
+}
+
+/// Where the upstream versions of a recipe are looked up
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Upstream {
+    /// A release-monitoring.org project, by id
+    ReleaseMonitoring(i64),
+    /// The releases of a GitHub repository, as `owner/repo`
+    Github(String),
+}
+
+impl Upstream {
+    /// The upstreams declared by a recipe's monitoring data, preferring
+    /// release-monitoring.org and only using GitHub without a project id
+    pub fn all(monitoring: &Monitoring) -> Vec<Self> {
+        match (monitoring.project_ids.as_slice(), &monitoring.github) {
+            ([], Some(repo)) => vec![Upstream::Github(repo.clone())],
+            (ids, _) => ids
+                .iter()
+                .map(|&id| Upstream::ReleaseMonitoring(id))
+                .collect(),
+        }
+    }
+}
+
+/// A recipe with a different upstream version
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RequiredUpdate {
+    pub source: String,
+    pub current_version: String,
+    pub latest_version: String,
+    /// The release-monitoring.org project the version was found in, which is
+    /// one of several for recipes bundling multiple upstreams
+    pub project_id: i64,
+    /// The GitHub repository the version was found in, for projects not on
+    /// release-monitoring.org
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github: Option<String>,
+}
+
+impl std::fmt::Display for Upstream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Upstream::ReleaseMonitoring(id) => write!(f, "release-monitoring.org project {id}"),
+            Upstream::Github(repo) => write!(f, "GitHub repository {repo}"),
+        }
+    }
+}
+
+/// An upstream that failed to be looked up
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Failure {
+    pub upstream: Upstream,
+    /// Whether the lookup gave up on the upstream still rate limiting requests
+    pub rate_limited: bool,
+    pub error: String,
+}
+
+impl Failure {
+    // This function records why an upstream failed to be looked up
+    fn new(upstream: Upstream, error: &data::Error) -> Self {
+        Self {
+            upstream,
+            rate_limited: matches!(error, data::Error::RateLimited(_)),
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Options for `find_updates`
+#[derive(Clone)]
+pub struct Options {
+    /// Release channel to select upstream versions from
+    pub channel: Channel,
+
+    /// Only select from the versions upstream marks as stable, for every recipe
+    /// rather than just those whose monitoring data asks for it
+    pub stable_only: bool,
+
+    /// Versioning scheme of recipes whose monitoring data doesn't declare one
+    pub default_scheme: Scheme,
+
+    /// Maximum number of concurrent requests to release-monitoring.org
+    pub jobs: usize,
+
+    /// Base URL of the release-monitoring.org API to look projects up in
+    pub release_monitoring_url: String,
+
+    /// How long cached versions are used before they are fetched again
+    pub cache_ttl: Duration,
+
+    // Invoked with the number of projects done and the total as lookups complete
+    on_progress: Option<Arc<dyn Fn(u64, u64) + Send + Sync>>,
+
+    // Serves fresh versions, and receives the versions fetched
+    cache: Option<Arc<Mutex<VersionCache>>>,
+
+    // Stops the lookup early once notified
+    interrupt: Option<Arc<Notify>>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            channel: Channel::default(),
+            stable_only: false,
+            default_scheme: Scheme::default(),
+            jobs: 32,
+            release_monitoring_url: data::updates::RELEASE_MONITORING_URL.to_string(),
+            cache_ttl: cache::DEFAULT_TTL,
+            on_progress: None,
+            cache: None,
+            interrupt: None,
+        }
+    }
+}
+
+impl Options {
+    /// Register a callback to be notified of how many projects have been looked up,
+    /// and out of how many
+    pub fn on_progress(mut self, callback: impl Fn(u64, u64) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Serve versions fetched less than `cache_ttl` ago from the cache, and add the
+    /// versions fetched to it, leaving it to the caller to save the cache
+    pub fn with_cache(mut self, cache: Arc<Mutex<VersionCache>>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Stop looking up projects once notified, reporting on those looked up so far
+    pub fn with_interrupt(mut self, interrupt: Arc<Notify>) -> Self {
+        self.interrupt = Some(interrupt);
+        self
+    }
+
+    // Report progress to the registered callback
+    fn progress(&self, done: u64, total: u64) {
+        if let Some(callback) = &self.on_progress {
+            callback(done, total);
+        }
+    }
+}
+
+/// The outcome of `find_updates`
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Recipes with a newer upstream version, sorted by name
+    pub updates: Vec<RequiredUpdate>,
+
+    /// Recipes whose upstream only has prereleases on the stable channel
+    pub no_stable: Vec<RequiredUpdate>,
+
+    /// Updates exceeding the recipe's `max_bump` policy, with the version held back
+    pub held_back: Vec<RequiredUpdate>,
+
+    /// Recipes whose upstream version is older than their own, which most likely
+    /// means the monitoring data points at the wrong project
+    pub downgrades: Vec<RequiredUpdate>,
+
+    /// Upstream versions of every project that was looked up, keyed by project id
+    pub latest_versions: HashMap<i64, VersionResponse>,
+
+    /// Upstream versions of every GitHub repository that was looked up
+    pub github_versions: HashMap<String, VersionResponse>,
+
+    /// Upstreams that failed to be looked up, and so weren't checked
+    pub failures: Vec<Failure>,
+
+    /// How many projects were fetched rather than served from the cache
+    pub fetched: usize,
+
+    /// Whether the lookup was cut short, so only part of the projects were checked
+    pub interrupted: bool,
+}
+
+impl Report {
+    /// The upstream versions found for the given upstream, if it was looked up
+    pub fn versions(&self, upstream: &Upstream) -> Option<&VersionResponse> {
+        match upstream {
+            Upstream::ReleaseMonitoring(project_id) => self.latest_versions.get(project_id),
+            Upstream::Github(repo) => self.github_versions.get(repo),
+        }
+    }
+}
+
+/// Group recipes by the release-monitoring.org project they are monitored by, so
+/// that each project only needs to be looked up once
+pub fn projects(recipes: &[Recipe]) -> HashMap<i64, Vec<&Recipe>> {
+    let mut projects = HashMap::<i64, Vec<&Recipe>>::new();
+    for recipe in recipes {
+        let ids = recipe.monitoring.iter().flat_map(|m| &m.project_ids);
+        for &project_id in ids {
+            projects.entry(project_id).or_default().push(recipe);
+        }
+    }
+    projects
+}
+
+/// Scan the recipe tree at `root` and find the recipes whose version differs from
+/// their upstream release, as by `scan` and `find_updates`
+pub async fn check(
+    client: &Client,
+    root: impl AsRef<Path>,
+    scan: &ScanOptions,
+    options: &Options,
+) -> Result<Report, Error> {
+    let scanned = recipes::scan::scan(root, scan)?;
+    Ok(find_updates(client, &scanned.recipes, options).await?)
+}
+
+/// Find the recipes whose version differs from their upstream release
+///
+/// Versions are fetched from release-monitoring.org, unless the options have a
+/// cache with fresh versions. When the options' interrupt is notified before all
+/// projects were fetched, the lookup stops early and the report covers the
+/// projects looked up so far.
+///
+/// Versions are compared using the scheme declared by the recipe's monitoring.yaml,
+/// falling back to the default scheme of the options.
+pub async fn find_updates(
+    client: &Client,
+    recipes: &[Recipe],
+    options: &Options,
+) -> Result<Report, data::Error> {
+    let projects = projects(recipes);
+    let repos = recipes
+        .iter()
+        .flat_map(|r| r.monitoring.iter().flat_map(Upstream::all))
+        .filter_map(|upstream| match upstream {
+            Upstream::Github(repo) => Some(repo),
+            Upstream::ReleaseMonitoring(_) => None,
+        })
+        .collect::<BTreeSet<_>>();
+    let total = (projects.len() + repos.len()) as u64;
+    let mut done = 0;
+    let mut report = Report::default();
+
+    // Serve fresh projects from the version cache, only fetching the rest
+    let now = cache::now();
+    if let Some(cache) = &options.cache {
+        let cache = cache.lock().unwrap();
+        for &project_id in projects.keys() {
+            if let Some(lv) = cache.fresh(project_id, now, options.cache_ttl) {
+                tracing::debug!(project_id, "using cached versions");
+                report.latest_versions.insert(project_id, lv.clone());
+                done += 1;
+            }
+        }
+    }
+    options.progress(done, total);
+    let mut stale = projects
+        .keys()
+        .filter(|project_id| !report.latest_versions.contains_key(*project_id))
+        .copied()
+        .collect::<Vec<_>>();
+    stale.sort();
+
+    // Fetch the upstream versions of the remaining projects, recording projects that
+    // failed to fetch. Everything fetched before an interrupt is kept.
+    let mut interrupt = std::pin::pin!(async {
+        match &options.interrupt {
+            Some(interrupt) => interrupt.notified().await,
+            None => std::future::pending().await,
+        }
+    });
+    let mut lookups = std::pin::pin!(data::updates::stream_latest_versions(
+        client,
+        &options.release_monitoring_url,
+        &stale,
+        options.jobs,
+    ));
+    loop {
+        let (chunk, result) = tokio::select! {
+            next = lookups.next() => match next {
+                Some(next) => next,
+                None => break,
+            },
+            _ = &mut interrupt => {
+                report.interrupted = true;
+                break;
+            }
+        };
+        done += chunk.len() as u64;
+        options.progress(done, total);
+
+        let mut found = match result {
+            Ok(found) => found,
+            Err(data::Error::NetworkDisabled) => return Err(data::Error::NetworkDisabled),
+            Err(e) => {
+                tracing::debug!(projects = ?chunk, error = %e, "failed to fetch versions");
+                for &project_id in chunk {
+                    let upstream = Upstream::ReleaseMonitoring(project_id);
+                    report.failures.push(Failure::new(upstream, &e));
+                }
+                continue;
+            }
+        };
+        for &project_id in chunk {
+            let Some(lv) = found.remove(&project_id) else {
+                let upstream = Upstream::ReleaseMonitoring(project_id);
+                let e = data::Error::ProjectNotFound(project_id);
+                report.failures.push(Failure::new(upstream, &e));
+                continue;
+            };
+            if let Some(cache) = &options.cache {
+                cache.lock().unwrap().entries.insert(
+                    project_id,
+                    cache::Entry {
+                        fetched_at: now,
+                        paths: projects[&project_id]
+                            .iter()
+                            .map(|r| r.path.clone())
+                            .collect(),
+                        versions: lv.clone(),
+                    },
+                );
+            }
+            report.latest_versions.insert(project_id, lv);
+            report.fetched += 1;
+        }
+    }
+
+    // Then look up the projects only published on GitHub, which aren't cached
+    if !report.interrupted && !repos.is_empty() {
+        let lookups = futures::stream::iter(&repos)
+            .map(|repo| async move {
+                let lv = data::updates::get_github_version(client, repo).await;
+                (repo.clone(), lv)
+            })
+            .buffer_unordered(options.jobs)
+            .collect::<Vec<_>>();
+        let results = tokio::select! {
+            results = lookups => results,
+            _ = &mut interrupt => {
+                report.interrupted = true;
+                vec![]
+            }
+        };
+
+        for (repo, result) in results {
+            match result {
+                Ok(lv) => {
+                    report.github_versions.insert(repo, normalize_tags(lv));
+                }
+                Err(data::Error::NetworkDisabled) => return Err(data::Error::NetworkDisabled),
+                Err(e) => {
+                    tracing::debug!(%repo, error = %e, "failed to fetch latest release");
+                    report
+                        .failures
+                        .push(Failure::new(Upstream::Github(repo), &e));
+                }
+            }
+            done += 1;
+        }
+        options.progress(done, total);
+    }
+    report.failures.sort_by(|a, b| a.upstream.cmp(&b.upstream));
+
+    // Compare each recipe against the versions fetched for its upstreams, so that
+    // recipes bundling several of them are reported for every one that differs
+    let mut updates = vec![];
+    let mut no_stable = vec![];
+    let mut held_back = vec![];
+    let mut downgrades = vec![];
+    for recipe in recipes {
+        let Some(m) = &recipe.monitoring else {
+            tracing::info!(recipe = %recipe.name, "skipping, no monitoring.yaml");
+            continue;
+        };
+        let upstreams = Upstream::all(m);
+        if upstreams.is_empty() {
+            tracing::info!(recipe = %recipe.name, "skipping, no project id or GitHub repo");
+        }
+        for upstream in upstreams {
+            let Some(lv) = report.versions(&upstream) else {
+                tracing::info!(recipe = %recipe.name, ?upstream, "skipping, no upstream versions");
+                continue;
+            };
+            let (project_id, github) = match upstream {
+                Upstream::ReleaseMonitoring(project_id) => (project_id, None),
+                Upstream::Github(repo) => (0, Some(repo)),
+            };
+            let stripped;
+            let lv = if m.has_version_affixes() {
+                stripped = strip_versions(lv, m);
+                &stripped
+            } else {
+                lv
+            };
+            let scheme = m.scheme.unwrap_or(options.default_scheme);
+            let channel = options.channel;
+            let stable_only = options.stable_only || m.stable_only;
+            // Epochs, VCS snapshots and packaging releases aren't known upstream
+            let current = version::normalize_version(&recipe.version);
+
+            // Create update info if versions differ
+            match select_version(lv, channel, stable_only) {
+                Some(Selection::Version(nv)) if scheme.compare(&nv, current).is_ne() => {
+                    let update = |latest_version| RequiredUpdate {
+                        source: recipe.name.clone(),
+                        current_version: recipe.version.clone(),
+                        latest_version,
+                        project_id,
+                        github: github.clone(),
+                    };
+
+                    // Upstream going backwards isn't an update, but a sign of the
+                    // wrong project being monitored
+                    if is_downgrade(scheme, current, &nv) {
+                        downgrades.push(update(nv));
+                        continue;
+                    }
+
+                    // Cap the suggestion at the recipe's update policy, noting the bigger bump
+                    let exceeded = m
+                        .max_bump
+                        .filter(|&p| scheme.bump(current, &nv).is_some_and(|b| b > p));
+                    match exceeded {
+                        Some(policy) => {
+                            if let Some(capped) =
+                                capped_version(lv, current, policy, channel, scheme)
+                            {
+                                updates.push(update(capped));
+                            }
+                            held_back.push(update(nv));
+                        }
+                        None => updates.push(update(nv)),
+                    }
+                }
+                Some(Selection::PrereleaseOnly(nv)) => no_stable.push(RequiredUpdate {
+                    source: recipe.name.clone(),
+                    current_version: recipe.version.clone(),
+                    latest_version: nv,
+                    project_id,
+                    github,
+                }),
+                Some(Selection::Version(_)) => {
+                    tracing::info!(recipe = %recipe.name, version = %recipe.version, "up to date");
+                }
+                None => tracing::info!(recipe = %recipe.name, "skipping, no upstream version"),
+            }
+        }
+    }
+    let order = |a: &RequiredUpdate, b: &RequiredUpdate| {
+        a.source
+            .cmp(&b.source)
+            .then_with(|| a.project_id.cmp(&b.project_id))
+    };
+    updates.sort_by(order);
+    no_stable.sort_by(order);
+    held_back.sort_by(order);
+    downgrades.sort_by(order);
+    report.updates = updates;
+    report.no_stable = no_stable;
+    report.held_back = held_back;
+    report.downgrades = downgrades;
+
+    Ok(report)
+}
+
+/// The upstream version selected for a recipe
+#[derive(Debug, PartialEq, Eq)]
+enum Selection {
+    /// A version suitable for the requested channel
+    Version(String),
+    /// Upstream only knows prereleases, so there is nothing stable to track yet
+    PrereleaseOnly(String),
+}
+
+// This function selects the upstream version to compare against, preferring
+// stable > latest > first available. On the stable channel prereleases are never
+// selected, and a prerelease-only upstream is reported as such. When stable_only
+// is set, only the versions upstream marks as stable are ever selected.
+fn select_version(lv: &VersionResponse, channel: Channel, stable_only: bool) -> Option<Selection> {
+    if let Some(stable) = lv.stable_versions.first() {
+        return Some(Selection::Version(stable.clone()));
+    }
+
+    let mut candidates = lv.latest_version.iter().chain(&lv.versions);
+    if stable_only {
+        return candidates.next().cloned().map(Selection::PrereleaseOnly);
+    }
+
+    match channel {
+        Channel::Latest => candidates.next().cloned().map(Selection::Version),
+        Channel::Stable => match candidates.clone().find(|v| !version::is_prerelease(v)) {
+            Some(v) => Some(Selection::Version(v.clone())),
+            None => candidates.next().cloned().map(Selection::PrereleaseOnly),
+        },
+    }
+}
+
+// This function checks whether the upstream version is older than the recipe's.
+// Only versions that start out numeric are ordered reliably enough to tell, so
+// anything else is never considered a downgrade.
+fn is_downgrade(scheme: Scheme, current: &str, latest: &str) -> bool {
+    let numeric = |v: &str| v.starts_with(|c: char| c.is_ascii_digit());
+    numeric(current) && numeric(latest) && scheme.compare(latest, current).is_lt()
+}
+
+// This function finds the newest upstream version within the given update policy,
+// for when the selected version would be too large a bump
+fn capped_version(
+    lv: &VersionResponse,
+    current: &str,
+    policy: Bump,
+    channel: Channel,
+    scheme: Scheme,
+) -> Option<String> {
+    let candidates = if lv.stable_versions.is_empty() {
+        lv.latest_version
+            .iter()
+            .chain(&lv.versions)
+            .filter(|v| channel == Channel::Latest || !version::is_prerelease(v))
+            .collect::<Vec<_>>()
+    } else {
+        lv.stable_versions.iter().collect()
+    };
+
+    candidates
+        .into_iter()
+        .filter(|v| scheme.bump(current, v).is_some_and(|b| b <= policy))
+        .max_by(|a, b| scheme.compare(a, b))
+        .cloned()
+}
+
+// This function reduces release tags to the versions they name, such as the
+// `1.2.3` of `v1.2.3`
+fn normalize_tags(lv: VersionResponse) -> VersionResponse {
+    let normalize = |versions: Vec<String>| {
+        versions
+            .iter()
+            .map(|v| version::normalize_version(v).to_string())
+            .collect()
+    };
+    VersionResponse {
+        latest_version: lv
+            .latest_version
+            .as_deref()
+            .map(|v| version::normalize_version(v).to_string()),
+        stable_versions: normalize(lv.stable_versions),
+        versions: normalize(lv.versions),
+    }
+}
+
+// This function strips the prefix and suffix declared in a recipe's monitoring
+// data from all upstream versions, such as the `v` of `v1.2.3`
+fn strip_versions(lv: &VersionResponse, monitoring: &Monitoring) -> VersionResponse {
+    let strip = |versions: &[String]| {
+        versions
+            .iter()
+            .map(|v| monitoring.strip_version(v).to_string())
+            .collect()
+    };
+    VersionResponse {
+        latest_version: lv
+            .latest_version
+            .as_deref()
+            .map(|v| monitoring.strip_version(v).to_string()),
+        stable_versions: strip(&lv.stable_versions),
+        versions: strip(&lv.versions),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::data::mock;
+
+    // This function writes a ypkg recipe with its monitoring data into the tree
+    fn write_recipe(root: &Path, name: &str, version: &str, project_id: i64) {
+        let dir = root.join(name);
+        fs::create_dir(&dir).unwrap();
+        fs::write(
+            dir.join("package.yml"),
+            format!("name: {name}\nversion: \"{version}\"\n"),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("monitoring.yaml"),
+            format!("releases:\n  id: {project_id}\n"),
+        )
+        .unwrap();
+    }
+
+    // This function builds the upstream versions of a project
+    fn versions(latest: Option<&str>, stable: &[&str], all: &[&str]) -> VersionResponse {
+        let owned = |versions: &[&str]| versions.iter().map(|v| v.to_string()).collect();
+        VersionResponse {
+            latest_version: latest.map(str::to_string),
+            stable_versions: owned(stable),
+            versions: owned(all),
+        }
+    }
+
+    // This function starts a release-monitoring.org server knowing the versions of
+    // the given projects, answering every lookup in the batched response shape
+    async fn versions_server(projects: &'static [(i64, &'static str)]) -> mock::Server {
+        mock::Server::start(move |_, target| {
+            let ids = target
+                .rsplit('=')
+                .next()
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|id| id.parse::<i64>().ok())
+                .collect::<Vec<_>>();
+            let items = projects
+                .iter()
+                .filter(|(id, _)| ids.contains(id))
+                .map(|(id, versions)| format!(r#"{{"project_id": {id}, {}"#, &versions[1..]))
+                .collect::<Vec<_>>();
+            mock::Response::json(format!(r#"{{"items": [{}]}}"#, items.join(",")))
+        })
+        .await
+    }
+
+    #[test]
+    fn older_upstream_is_a_downgrade() {
+        assert!(is_downgrade(Scheme::Auto, "2.0.1", "1.9.0"));
+        assert!(is_downgrade(Scheme::Semver, "1.10", "1.9"));
+        assert!(!is_downgrade(Scheme::Auto, "1.9.0", "2.0.1"));
+    }
+
+    #[test]
+    fn equal_versions_are_not_a_downgrade() {
+        assert!(!is_downgrade(Scheme::Auto, "1.2.3", "1.2.3"));
+        assert!(!is_downgrade(Scheme::Date, "20240501", "2024.05.01"));
+    }
+
+    #[test]
+    fn non_numeric_versions_are_never_a_downgrade() {
+        assert!(!is_downgrade(Scheme::Auto, "2.0", "v1.0"));
+        assert!(!is_downgrade(Scheme::Auto, "stable", "1.0"));
+    }
+
+    #[test]
+    fn epoch_is_stripped_before_comparing() {
+        // Without normalizing, the epoch reads as the major version
+        let current = version::normalize_version("1:2.0.0");
+        assert_eq!(current, "2.0.0");
+        assert!(!is_downgrade(Scheme::Auto, current, "2.1.0"));
+        assert!(is_downgrade(Scheme::Auto, current, "1.9.0"));
+    }
+
+    #[test]
+    fn stable_versions_are_preferred() {
+        let lv = versions(Some("2.0rc1"), &["1.9"], &["2.0rc1", "1.9"]);
+
+        for channel in [Channel::Latest, Channel::Stable] {
+            assert_eq!(
+                select_version(&lv, channel, false),
+                Some(Selection::Version("1.9".to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn prereleases_only_count_on_the_latest_channel() {
+        let lv = versions(Some("2.0rc1"), &[], &["2.0rc1", "1.9"]);
+
+        assert_eq!(
+            select_version(&lv, Channel::Latest, false),
+            Some(Selection::Version("2.0rc1".to_string()))
+        );
+        assert_eq!(
+            select_version(&lv, Channel::Stable, false),
+            Some(Selection::Version("1.9".to_string()))
+        );
+    }
+
+    #[test]
+    fn prerelease_only_upstream_is_reported_as_such() {
+        let lv = versions(Some("2.0rc1"), &[], &["2.0rc1", "2.0beta2"]);
+
+        assert_eq!(
+            select_version(&lv, Channel::Stable, false),
+            Some(Selection::PrereleaseOnly("2.0rc1".to_string()))
+        );
+        // Without stable versions upstream, stable_only never selects a version
+        assert_eq!(
+            select_version(&lv, Channel::Latest, true),
+            Some(Selection::PrereleaseOnly("2.0rc1".to_string()))
+        );
+        assert_eq!(
+            select_version(&versions(None, &[], &[]), Channel::Latest, false),
+            None
+        );
+    }
+
+    #[test]
+    fn stable_only_never_falls_back() {
+        // Upstream hasn't marked any version as stable, though 1.1 looks like one
+        let lv = versions(Some("1.1"), &[], &["1.1", "1.0"]);
+        for channel in [Channel::Latest, Channel::Stable] {
+            assert_eq!(
+                select_version(&lv, channel, false),
+                Some(Selection::Version("1.1".to_string()))
+            );
+            assert_eq!(
+                select_version(&lv, channel, true),
+                Some(Selection::PrereleaseOnly("1.1".to_string()))
+            );
+        }
+
+        // With stable versions, both modes select the same one
+        let lv = versions(Some("1.2"), &["1.1"], &["1.2", "1.1"]);
+        assert_eq!(
+            select_version(&lv, Channel::Latest, false),
+            select_version(&lv, Channel::Latest, true)
+        );
+    }
+
+    #[tokio::test]
+    async fn monitoring_stable_only_reports_no_stable_release() {
+        let root = tempfile::tempdir().unwrap();
+        write_recipe(root.path(), "nano", "7.2", 2046);
+        write_recipe(root.path(), "vim", "9.1", 5092);
+        fs::write(
+            root.path().join("vim/monitoring.yaml"),
+            "releases:\n  id: 5092\n  stable_only: true\n",
+        )
+        .unwrap();
+        let server = versions_server(&[
+            (2046, r#"{"latest_version": "10.0", "stable_versions": []}"#),
+            (5092, r#"{"latest_version": "10.0", "stable_versions": []}"#),
+        ])
+        .await;
+
+        let options = Options {
+            release_monitoring_url: server.url.clone(),
+            ..Default::default()
+        };
+        let report = check(
+            &Client::new(false),
+            root.path(),
+            &ScanOptions::default(),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        let sources = |updates: &[RequiredUpdate]| {
+            updates.iter().map(|u| u.source.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(sources(&report.updates), ["nano"]);
+        assert_eq!(sources(&report.no_stable), ["vim"]);
+        assert_eq!(report.no_stable[0].latest_version, "10.0");
+    }
+
+    #[tokio::test]
+    async fn check_reports_updates_and_failures() {
+        let root = tempfile::tempdir().unwrap();
+        write_recipe(root.path(), "nano", "7.2", 2046);
+        write_recipe(root.path(), "vim", "9.1", 5092);
+        write_recipe(root.path(), "gone", "1.0", 404);
+        let server = versions_server(&[
+            (2046, r#"{"stable_versions": ["8.0", "7.2"]}"#),
+            (5092, r#"{"stable_versions": ["9.1"]}"#),
+        ])
+        .await;
+
+        let options = Options {
+            release_monitoring_url: server.url.clone(),
+            ..Default::default()
+        };
+        let report = check(
+            &Client::new(false),
+            root.path(),
+            &ScanOptions::default(),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        // All three projects fit in a single lookup
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(report.updates.len(), 1);
+        assert_eq!(report.updates[0].source, "nano");
+        assert_eq!(report.updates[0].current_version, "7.2");
+        assert_eq!(report.updates[0].latest_version, "8.0");
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(
+            report.failures[0].upstream,
+            Upstream::ReleaseMonitoring(404)
+        );
+        assert!(!report.failures[0].rate_limited);
+        assert_eq!(report.fetched, 2);
+        assert!(!report.interrupted);
+    }
+
+    #[tokio::test]
+    async fn version_prefix_removes_false_updates() {
+        let root = tempfile::tempdir().unwrap();
+        write_recipe(root.path(), "ripgrep", "14.1.0", 1000);
+        write_recipe(root.path(), "fd", "10.1.0", 2000);
+        fs::write(
+            root.path().join("fd/monitoring.yaml"),
+            "releases:\n  id: 2000\n  version_prefix: v\n",
+        )
+        .unwrap();
+        let server = versions_server(&[
+            (1000, r#"{"stable_versions": ["v14.1.0"]}"#),
+            (2000, r#"{"stable_versions": ["v10.1.0"]}"#),
+        ])
+        .await;
+
+        let options = Options {
+            release_monitoring_url: server.url.clone(),
+            ..Default::default()
+        };
+        let report = check(
+            &Client::new(false),
+            root.path(),
+            &ScanOptions::default(),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        // Only the recipe without a declared prefix is reported as outdated
+        assert_eq!(report.updates.len(), 1);
+        assert_eq!(report.updates[0].source, "ripgrep");
+        assert_eq!(report.updates[0].latest_version, "v14.1.0");
+    }
+}
@@ -0,0 +1,478 @@
+// SPDX-FileCopyrightText: Copyright © 2020-2024 Serpent OS Developers
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Version string helpers
+
+use std::{cmp::Ordering, str::FromStr};
+
+/// Markers commonly used by upstreams to denote a prerelease
+const PRERELEASE_MARKERS: &[&str] = &["alpha", "beta", "rc", "pre", "preview", "dev", "snapshot"];
+
+/// Markers appended to recipe versions built from a VCS snapshot, as in `1.4.0+git20240501`
+const VCS_DELIMITERS: &[&str] = &["+git", "+vcs", "+mur"];
+
+/// Reduce a recipe version or release tag to the bare upstream version
+///
+/// A leading epoch, as in `2:1.4.0`, a tag prefix such as the `v` of `v1.4.0` or
+/// the `release-` of `release-1.4.0`, a VCS snapshot marker such as `+git20240501`
+/// and a trailing packaging release such as `-r2` are stripped, so all of them
+/// compare equal to the upstream `1.4.0`.
+pub fn normalize_version(version: &str) -> &str {
+    let version = match version.split_once(':') {
+        Some((epoch, rest)) if !epoch.is_empty() && epoch.chars().all(|c| c.is_ascii_digit()) => {
+            rest
+        }
+        _ => version,
+    };
+    let version = strip_tag_prefix(version);
+
+    let version = VCS_DELIMITERS
+        .iter()
+        .filter_map(|delimiter| version.find(delimiter))
+        .min()
+        .map_or(version, |end| &version[..end]);
+
+    match version.rsplit_once("-r") {
+        Some((rest, release))
+            if !rest.is_empty()
+                && !release.is_empty()
+                && release.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            rest
+        }
+        _ => version,
+    }
+}
+
+// This function strips the name a tag gives its version, which is a `v` or a word
+// ending in `-` or `_`, optionally followed by a `v`, as in `release-v1.2`.
+// Anything else before the first digit belongs to the version and is kept.
+fn strip_tag_prefix(version: &str) -> &str {
+    let Some(start) = version.find(|c: char| c.is_ascii_digit()) else {
+        return version;
+    };
+    let (prefix, rest) = version.split_at(start);
+    let name = prefix.strip_suffix(['v', 'V']).unwrap_or(prefix);
+    let tagged = name.is_empty()
+        || (name.ends_with(['-', '_'])
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphabetic() || c == '-' || c == '_'));
+
+    if tagged {
+        rest
+    } else {
+        version
+    }
+}
+
+/// Determine whether a version string looks like a prerelease
+///
+/// The version is split into its alphabetic runs, so `1.0rc1`, `1.0-beta.2` and
+/// `2.0.0-alpha` are all prereleases, while `1.2.3` or `2024.05` are not. The single
+/// letter shorthands `a` and `b` are only honoured between digits, as in `3.13.0a4`.
+pub fn is_prerelease(version: &str) -> bool {
+    let version = version.to_lowercase();
+    let chars = version.chars().collect::<Vec<_>>();
+
+    let marker = version
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .any(|word| PRERELEASE_MARKERS.contains(&word));
+
+    let shorthand = chars
+        .windows(3)
+        .any(|w| w[0].is_ascii_digit() && (w[1] == 'a' || w[1] == 'b') && w[2].is_ascii_digit());
+
+    marker || shorthand
+}
+
+/// A run of digits or letters within a version string
+#[derive(Debug, PartialEq, Eq)]
+enum Segment<'a> {
+    Number(&'a str),
+    Text(&'a str),
+}
+
+impl Ord for Segment<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Segment::Number(a), Segment::Number(b)) => {
+                // Compare without parsing so arbitrarily long numbers can't overflow
+                let a = a.trim_start_matches('0');
+                let b = b.trim_start_matches('0');
+                a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+            }
+            (Segment::Text(a), Segment::Text(b)) => a.cmp(b),
+            (Segment::Number(_), Segment::Text(_)) => Ordering::Greater,
+            (Segment::Text(_), Segment::Number(_)) => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for Segment<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Split a version into digit and letter runs, dropping separators
+fn segments(version: &str) -> Vec<Segment<'_>> {
+    let mut ret = vec![];
+    let mut rest = version;
+
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric()) {
+        rest = &rest[start..];
+        let numeric = rest.starts_with(|c: char| c.is_ascii_digit());
+        let end = rest
+            .find(|c: char| {
+                if numeric {
+                    !c.is_ascii_digit()
+                } else {
+                    !c.is_ascii_alphabetic()
+                }
+            })
+            .unwrap_or(rest.len());
+
+        let (segment, tail) = rest.split_at(end);
+        ret.push(if numeric {
+            Segment::Number(segment)
+        } else {
+            Segment::Text(segment)
+        });
+        rest = tail;
+    }
+
+    ret
+}
+
+/// Compare two version strings
+///
+/// Versions are split into runs of digits and letters, with numeric runs compared by
+/// value so that `1.10` sorts after `1.9`. When one version is a prefix of the other,
+/// trailing letters mark a prerelease, so `1.0rc1` sorts before `1.0` while `1.0.1`
+/// sorts after it.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let a = segments(a);
+    let b = segments(b);
+
+    for (x, y) in a.iter().zip(&b) {
+        match x.cmp(y) {
+            Ordering::Equal => continue,
+            ordering => return ordering,
+        }
+    }
+
+    match (a.get(b.len()), b.get(a.len())) {
+        (Some(Segment::Text(_)), _) => Ordering::Less,
+        (_, Some(Segment::Text(_))) => Ordering::Greater,
+        _ => a.len().cmp(&b.len()),
+    }
+}
+
+/// The kind of change between two versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+/// Determine the kind of version bump going from `from` to `to`
+///
+/// The first differing component decides the kind of bump, so `1.2.3` to `2.0.0` is a
+/// major bump, `1.2.3` to `1.3.0` a minor one, and anything after that a patch. Returns
+/// `None` when `to` isn't newer than `from`.
+pub fn bump(from: &str, to: &str) -> Option<Bump> {
+    if compare(from, to) != Ordering::Less {
+        return None;
+    }
+
+    let from = segments(from);
+    let to = segments(to);
+    let differs = from
+        .iter()
+        .zip(&to)
+        .position(|(a, b)| a.cmp(b) != Ordering::Equal)
+        .unwrap_or(from.len().min(to.len()));
+
+    Some(match differs {
+        0 => Bump::Major,
+        1 => Bump::Minor,
+        _ => Bump::Patch,
+    })
+}
+
+/// The versioning scheme used to interpret and compare versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    /// Guess the scheme from the versions being compared
+    #[default]
+    Auto,
+    /// Dotted numeric versions such as `1.2.3`
+    Semver,
+    /// Date based versions such as `20240501` or `2024.05.01`
+    Date,
+}
+
+impl Scheme {
+    // Resolve `Auto` to a concrete scheme for the given versions
+    fn resolve(self, a: &str, b: &str) -> Scheme {
+        match self {
+            Scheme::Auto if is_date(a) && is_date(b) => Scheme::Date,
+            Scheme::Auto => Scheme::Semver,
+            scheme => scheme,
+        }
+    }
+
+    /// Compare two versions according to this scheme
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        match self.resolve(a, b) {
+            Scheme::Date => {
+                let (a, b) = (date_digits(a), date_digits(b));
+                Segment::Number(&a).cmp(&Segment::Number(&b))
+            }
+            _ => compare(a, b),
+        }
+    }
+
+    /// Determine the kind of version bump going from `from` to `to` according to this scheme
+    ///
+    /// Date based versions carry no compatibility promise, so any newer date is a major bump.
+    pub fn bump(self, from: &str, to: &str) -> Option<Bump> {
+        match self.resolve(from, to) {
+            Scheme::Date => self.compare(from, to).is_lt().then_some(Bump::Major),
+            _ => bump(from, to),
+        }
+    }
+}
+
+impl FromStr for Scheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Scheme::Auto),
+            "semver" => Ok(Scheme::Semver),
+            "date" => Ok(Scheme::Date),
+            _ => Err(format!(
+                "unknown version scheme `{s}`, expected semver, date or auto"
+            )),
+        }
+    }
+}
+
+// Whether a version looks like a date, i.e. only digits and separators with a
+// leading plausible year
+fn is_date(version: &str) -> bool {
+    let first = version
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .unwrap_or_default();
+    if !matches!(first.len(), 4 | 6 | 8 | 12 | 14) {
+        return false;
+    }
+
+    version
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '_'))
+        && matches!(first[..4].parse::<u32>(), Ok(1990..=2099))
+}
+
+// Flatten a date based version into a single digit string, padding the components
+// after the year so that `2024.5.1` and `20240501` are equivalent
+fn date_digits(version: &str) -> String {
+    version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| !s.is_empty())
+        .enumerate()
+        .map(|(i, s)| {
+            if i > 0 && s.len() < 2 {
+                format!("0{s}")
+            } else {
+                s.to_string()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_version_reduces_to_the_upstream_version() {
+        let cases = [
+            // Epochs
+            ("2:1.4.0", "1.4.0"),
+            ("10:1.4.0", "1.4.0"),
+            (":1.4.0", ":1.4.0"),
+            ("a:1.4.0", "a:1.4.0"),
+            // VCS snapshots
+            ("1.4.0+git20240501", "1.4.0"),
+            ("1.4.0+vcs5", "1.4.0"),
+            ("1.4.0+mur.1+git2", "1.4.0"),
+            ("1.4.0+build5", "1.4.0+build5"),
+            // Packaging releases
+            ("1.4.0-r2", "1.4.0"),
+            ("1.4.0-r", "1.4.0-r"),
+            ("1.4.0-rc1", "1.4.0-rc1"),
+            // All at once
+            ("1:1.4.0+git20240501-r3", "1.4.0"),
+            ("1.4.0", "1.4.0"),
+        ];
+        for (version, expected) in cases {
+            assert_eq!(normalize_version(version), expected, "{version}");
+        }
+    }
+
+    #[test]
+    fn normalize_version_strips_tag_prefixes() {
+        let cases = [
+            ("v1.2.3", "1.2.3"),
+            ("V1.2.3", "1.2.3"),
+            ("release-1.2", "1.2"),
+            ("release_1.2", "1.2"),
+            ("release-v1.2", "1.2"),
+            ("nano-7.2", "7.2"),
+            ("1.2.3", "1.2.3"),
+            // Not a tag name, so kept as part of the version
+            ("r1.2", "r1.2"),
+            ("1.2-v3", "1.2-v3"),
+            ("stable", "stable"),
+        ];
+        for (version, expected) in cases {
+            assert_eq!(normalize_version(version), expected, "{version}");
+        }
+    }
+
+    #[test]
+    fn normalized_recipe_versions_compare_as_upstream() {
+        // A recipe tracking tags as `v2` is newer than upstream 1.5, as the bare `2` is
+        let current = normalize_version("v2");
+        assert_eq!(current, "2");
+        assert_eq!(compare(current, "1.5"), Ordering::Greater);
+        assert_eq!(compare(current, "2"), Ordering::Equal);
+        assert_eq!(bump(current, "3"), Some(Bump::Major));
+        // Left as is, the letter would sort before any number
+        assert_eq!(compare("v2", "1.5"), Ordering::Less);
+    }
+
+    #[test]
+    fn segments_order_numbers_by_value() {
+        use Segment::{Number, Text};
+
+        assert_eq!(Number("10").cmp(&Number("9")), Ordering::Greater);
+        assert_eq!(Number("010").cmp(&Number("10")), Ordering::Equal);
+        assert_eq!(
+            Number("99999999999999999999999").cmp(&Number("99999999999999999999998")),
+            Ordering::Greater
+        );
+        assert_eq!(Number("1").cmp(&Text("rc")), Ordering::Greater);
+        assert_eq!(Text("alpha").cmp(&Text("beta")), Ordering::Less);
+        assert_eq!(
+            segments("1.10rc2"),
+            [Number("1"), Number("10"), Text("rc"), Number("2")]
+        );
+    }
+
+    #[test]
+    fn compare_orders_versions() {
+        let cases = [
+            ("1.10", "1.9", Ordering::Greater),
+            ("1.2.3", "1.2.3", Ordering::Equal),
+            ("1.0rc1", "1.0", Ordering::Less),
+            ("1.0-beta.2", "1.0-beta.10", Ordering::Less),
+            ("1.0.1", "1.0", Ordering::Greater),
+            ("1.0", "1.0.0", Ordering::Less),
+            ("2.0", "10.0", Ordering::Less),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(compare(a, b), expected, "{a} vs {b}");
+            assert_eq!(compare(b, a), expected.reverse(), "{b} vs {a}");
+        }
+    }
+
+    #[test]
+    fn bump_is_decided_by_the_first_difference() {
+        let cases = [
+            ("1.2.3", "2.0.0", Some(Bump::Major)),
+            ("1.2.3", "1.3.0", Some(Bump::Minor)),
+            ("1.2.3", "1.2.4", Some(Bump::Patch)),
+            ("1.2.3", "1.2.3.1", Some(Bump::Patch)),
+            ("1.9", "1.10", Some(Bump::Minor)),
+            ("1.2.3", "1.2.3", None),
+            ("1.2.3", "1.2.2", None),
+        ];
+        for (from, to, expected) in cases {
+            assert_eq!(bump(from, to), expected, "{from} -> {to}");
+        }
+    }
+
+    #[test]
+    fn schemes_compare_and_bump_dates() {
+        // Auto only treats versions as dates when both are
+        assert_eq!(
+            Scheme::Auto.compare("2024.5.1", "20240501"),
+            Ordering::Equal
+        );
+        assert_eq!(
+            Scheme::Date.compare("2024.5.2", "20240501"),
+            Ordering::Greater
+        );
+        assert_eq!(Scheme::Auto.compare("2024.05", "1.2"), Ordering::Greater);
+        assert_eq!(
+            Scheme::Semver.compare("2024.05.01", "20240502"),
+            Ordering::Less
+        );
+
+        assert_eq!(Scheme::Auto.bump("20240501", "20240601"), Some(Bump::Major));
+        assert_eq!(Scheme::Date.bump("2024.06.01", "2024.05.01"), None);
+        assert_eq!(Scheme::Semver.bump("2024.05", "2024.06"), Some(Bump::Minor));
+        assert_eq!(Scheme::Auto.bump("1.2.3", "1.2.4"), Some(Bump::Patch));
+    }
+
+    #[test]
+    fn scheme_from_str() {
+        assert_eq!("date".parse::<Scheme>(), Ok(Scheme::Date));
+        assert_eq!("semver".parse::<Scheme>(), Ok(Scheme::Semver));
+        assert_eq!("auto".parse::<Scheme>(), Ok(Scheme::Auto));
+        assert!("calver".parse::<Scheme>().is_err());
+    }
+
+    #[test]
+    fn is_prerelease_finds_markers() {
+        for version in [
+            "1.0rc1",
+            "1.0-beta.2",
+            "2.0.0-alpha",
+            "3.13.0a4",
+            "1.1b2",
+            "6.0-PRE",
+        ] {
+            assert!(is_prerelease(version), "{version}");
+        }
+        for version in ["1.2.3", "2024.05", "1.0a", "1.0-alphabet", "2.4.1-r2"] {
+            assert!(!is_prerelease(version), "{version}");
+        }
+    }
+
+    #[test]
+    fn is_date_requires_a_plausible_year() {
+        for version in ["20240501", "2024.05.01", "2024-5-1", "202405", "2024"] {
+            assert!(is_date(version), "{version}");
+        }
+        for version in ["1.2.3", "3000.01", "20240501a", "12345", "1989.01"] {
+            assert!(!is_date(version), "{version}");
+        }
+    }
+
+    #[test]
+    fn date_digits_pads_components() {
+        assert_eq!(date_digits("2024.5.1"), "20240501");
+        assert_eq!(date_digits("2024-05-01"), "20240501");
+        assert_eq!(date_digits("20240501"), "20240501");
+    }
+}